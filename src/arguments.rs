@@ -1,29 +1,85 @@
+use chrono::NaiveDate;
 use clap::Parser;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[clap(version = "0.1", author = "Dustin Knopoff <rust@knopoff.dev>")]
 pub struct Opts {
-    /// One of stdout, alfred, or json
+    /// One of stdout, alfred, json, ndjson, csv, tsv, table, markdown, rofi, dmenu, fzf, or quickfix
     #[clap(default_value = "stdout")]
     pub format: OutFormat,
+    #[clap(flatten)]
+    pub output: OutputOptions,
     /// Before performing actions, find new wikis and add to the database
     #[clap(long, short)]
     pub calculate: bool,
     #[clap(long)]
     pub config_dir: Option<String>,
+    /// Open the top result of a query in $VISUAL or $EDITOR, or pick from the results in a terminal
+    #[clap(long, short)]
+    pub open: bool,
     #[clap(subcommand)]
     pub subcmd: SubCommand,
 }
 
+/// Options shaping how results are written out
+#[derive(Parser, Debug)]
+#[clap(about = None, long_about = None)]
+pub struct OutputOptions {
+    /// Comma separated columns for the csv, tsv and table formats,
+    /// from id, title, path, created, modified, tags, and snippet
+    #[clap(long, default_value = "title,path")]
+    pub columns: Columns,
+    /// Comma separated fields written by the json and ndjson formats, every field by default,
+    /// and passed on to alfred as workflow variables. Any of zettel_id, timestamp, title,
+    /// file_path, modified, tags, aliases, headers, words, links_out, links_in, and snippet
+    #[clap(long)]
+    pub fields: Option<Fields>,
+    /// Write each result with a template such as '{title} ({created:%Y-%m-%d}) -> {path}',
+    /// or the name of a template in config.toml. Replaces the output format
+    #[clap(long)]
+    pub template: Option<String>,
+    /// Links in the markdown format, either wiki for `[[wikilinks]]` or relative for
+    /// `[title](path)`. Defaults to `markdown-links` in config.toml
+    #[clap(long)]
+    pub links: Option<LinkStyle>,
+    /// Group the markdown format under a heading for each tag or creation date
+    #[clap(long)]
+    pub group_by: Option<GroupBy>,
+    /// Directory relative links in the markdown format start from, the wiki by default
+    #[clap(long)]
+    pub relative_to: Option<PathBuf>,
+    /// When to color stdout, one of auto, always, or never. Auto colors a terminal unless
+    /// NO_COLOR is set
+    #[clap(long, default_value = "auto")]
+    pub color: ColorChoice,
+    /// What was searched for, offered as the title of a new note by alfred when nothing is found
+    #[clap(skip)]
+    pub query: Option<String>,
+    /// Pattern to look for in each result besides the terms matched in its snippet,
+    /// used to find the line to open or the lines listed by quickfix
+    #[clap(skip)]
+    pub needle: Option<String>,
+}
+
 #[derive(Parser, Debug)]
 pub enum OutFormat {
     StdOut,
     Alfred,
     JSON,
+    Ndjson,
+    Csv,
+    Tsv,
+    Table,
+    Markdown,
+    Rofi,
+    Dmenu,
+    Fzf,
+    Quickfix,
 }
 
 impl Display for OutFormat {
@@ -32,6 +88,15 @@ impl Display for OutFormat {
             OutFormat::StdOut => write!(f, "stdout"),
             OutFormat::Alfred => write!(f, "alfred"),
             OutFormat::JSON => write!(f, "json"),
+            OutFormat::Ndjson => write!(f, "ndjson"),
+            OutFormat::Csv => write!(f, "csv"),
+            OutFormat::Tsv => write!(f, "tsv"),
+            OutFormat::Table => write!(f, "table"),
+            OutFormat::Markdown => write!(f, "markdown"),
+            OutFormat::Rofi => write!(f, "rofi"),
+            OutFormat::Dmenu => write!(f, "dmenu"),
+            OutFormat::Fzf => write!(f, "fzf"),
+            OutFormat::Quickfix => write!(f, "quickfix"),
         }
     }
 }
@@ -44,21 +109,244 @@ impl FromStr for OutFormat {
             "stdout" => Ok(OutFormat::StdOut),
             "alfred" => Ok(OutFormat::Alfred),
             "json" => Ok(OutFormat::JSON),
-            _ => Err(anyhow::anyhow!("{} is not stdout, alfred, or json", s)),
+            "ndjson" => Ok(OutFormat::Ndjson),
+            "csv" => Ok(OutFormat::Csv),
+            "tsv" => Ok(OutFormat::Tsv),
+            "table" => Ok(OutFormat::Table),
+            "markdown" | "md" => Ok(OutFormat::Markdown),
+            "rofi" => Ok(OutFormat::Rofi),
+            "dmenu" => Ok(OutFormat::Dmenu),
+            "fzf" => Ok(OutFormat::Fzf),
+            "quickfix" | "grep" => Ok(OutFormat::Quickfix),
+            _ => Err(anyhow::anyhow!(
+                "{} is not stdout, alfred, json, ndjson, csv, tsv, table, markdown, rofi, dmenu, fzf, or quickfix",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether to color stdout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorChoice::Auto => write!(f, "auto"),
+            ColorChoice::Always => write!(f, "always"),
+            ColorChoice::Never => write!(f, "never"),
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(anyhow::anyhow!("{} is not auto, always, or never", s)),
+        }
+    }
+}
+
+/// How the markdown format links to zettels
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// `[[file name]]`
+    #[default]
+    Wiki,
+    /// `[title](relative/path.md)`
+    Relative,
+}
+
+impl Display for LinkStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkStyle::Wiki => write!(f, "wiki"),
+            LinkStyle::Relative => write!(f, "relative"),
         }
     }
 }
 
+impl FromStr for LinkStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "wiki" => Ok(LinkStyle::Wiki),
+            "relative" => Ok(LinkStyle::Relative),
+            _ => Err(anyhow::anyhow!("{} is not wiki or relative", s)),
+        }
+    }
+}
+
+/// Headings the markdown format groups results under
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// One heading per tag, zettels with several tags are listed under each
+    Tag,
+    /// One heading per day zettels were created on
+    Date,
+}
+
+impl Display for GroupBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupBy::Tag => write!(f, "tag"),
+            GroupBy::Date => write!(f, "date"),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "tag" => Ok(GroupBy::Tag),
+            "date" => Ok(GroupBy::Date),
+            _ => Err(anyhow::anyhow!("{} is not tag or date", s)),
+        }
+    }
+}
+
+/// A field of a zettel shown by the csv, tsv and table formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Title,
+    Path,
+    Created,
+    Modified,
+    Tags,
+    Snippet,
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Column::Id => write!(f, "id"),
+            Column::Title => write!(f, "title"),
+            Column::Path => write!(f, "path"),
+            Column::Created => write!(f, "created"),
+            Column::Modified => write!(f, "modified"),
+            Column::Tags => write!(f, "tags"),
+            Column::Snippet => write!(f, "snippet"),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_ref() {
+            "id" => Ok(Column::Id),
+            "title" => Ok(Column::Title),
+            "path" => Ok(Column::Path),
+            "created" => Ok(Column::Created),
+            "modified" => Ok(Column::Modified),
+            "tags" => Ok(Column::Tags),
+            "snippet" => Ok(Column::Snippet),
+            _ => Err(anyhow::anyhow!(
+                "{} is not id, title, path, created, modified, tags, or snippet",
+                s
+            )),
+        }
+    }
+}
+
+/// Comma separated list of [Column]s
+#[derive(Debug, Clone)]
+pub struct Columns(pub Vec<Column>);
+
+impl FromStr for Columns {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Columns(
+            s.split(',')
+                .map(Column::from_str)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+/// Comma separated list of the fields of a [Zettel](crate::db::Zettel)
+#[derive(Debug, Clone)]
+pub struct Fields(pub Vec<String>);
+
+impl FromStr for Fields {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Fields(
+            s.split(',')
+                .map(|field| {
+                    let field = match field.trim() {
+                        "id" => "zettel_id",
+                        "path" => "file_path",
+                        "created" => "timestamp",
+                        field => field,
+                    };
+                    if crate::db::Zettel::FIELDS.contains(&field) {
+                        Ok(field.to_string())
+                    } else {
+                        Err(anyhow::anyhow!(
+                            "{} is not one of {}",
+                            field,
+                            crate::db::Zettel::FIELDS.join(", ")
+                        ))
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
 #[derive(Parser, Debug)]
 pub enum SubCommand {
     /// Search all documents in your wiki
     FullText(Search),
+    /// Fuzzy match titles, aliases and file names for quick-open
+    Find(Search),
     /// Find all zettels with matching tag
-    Tags(Search),
+    Tags(TagSearch),
+    /// List every tag with its zettel count and when it was last used
+    TagIndex(TagIndex),
+    /// Rename or merge tags across the wiki
+    Tag(TagEdit),
+    /// Find the tags most often used alongside a tag
+    RelatedTags(RelatedTags),
+    /// Export how many zettels share each pair of tags
+    TagMatrix,
     /// Find backlinks
     Links(Search),
     /// Creates a database storing your metadata about your zettels
     Create,
+    /// Create a new zettel from a template
+    New(New),
+    /// Append text from stdin to the inbox or an existing zettel
+    Capture(Capture),
+    /// Open or create the daily note
+    Daily(Periodic),
+    /// Open or create the weekly note
+    Weekly(Periodic),
+    /// Open or create the monthly note
+    Monthly(Periodic),
+    /// Print a zettel for fzf's preview window, or its path with --path
+    Preview(Preview),
+    /// Show everything known about a zettel: metadata, outline, links and backlinks
+    Show(Show),
     /// Update all or some of the database
     Update(Update),
     /// Setup a notifier to update the database on file changes
@@ -68,6 +356,171 @@ pub enum SubCommand {
 #[derive(Parser, Debug)]
 pub struct Search {
     pub text: String,
+    #[clap(flatten)]
+    pub options: QueryOptions,
+}
+
+#[derive(Parser, Debug)]
+pub struct TagSearch {
+    /// Tag to search for, with or without the leading `#`. Nested tags are included,
+    /// so `lang` also matches `lang/rust`
+    pub text: String,
+    /// Match every tag starting with the text
+    #[clap(long)]
+    pub prefix: bool,
+    #[clap(flatten)]
+    pub options: QueryOptions,
+}
+
+#[derive(Parser, Debug)]
+pub struct TagIndex {
+    #[clap(flatten)]
+    pub options: QueryOptions,
+}
+
+#[derive(Parser, Debug)]
+pub struct RelatedTags {
+    pub tag: String,
+    #[clap(flatten)]
+    pub options: QueryOptions,
+}
+
+#[derive(Parser, Debug)]
+pub struct TagEdit {
+    #[clap(subcommand)]
+    pub subcmd: TagCommand,
+}
+
+#[derive(Parser, Debug)]
+pub enum TagCommand {
+    /// Rename a tag, and every tag nested under it, in every zettel
+    Rename(TagRename),
+    /// Merge several tags, and every tag nested under them, into one in every zettel
+    Merge(TagMerge),
+}
+
+#[derive(Parser, Debug)]
+pub struct TagRename {
+    pub old: String,
+    pub new: String,
+    /// Print the changes that would be made without writing them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct TagMerge {
+    #[clap(required = true)]
+    pub tags: Vec<String>,
+    /// Tag to merge into
+    #[clap(long)]
+    pub into: String,
+    /// Print the changes that would be made without writing them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// Paging and ordering shared by every query
+#[derive(Parser, Debug)]
+pub struct QueryOptions {
+    /// Maximum number of results to return
+    #[clap(long, short)]
+    pub limit: Option<u32>,
+    /// Number of results to skip
+    #[clap(long, default_value = "0")]
+    pub offset: u32,
+    /// One of relevance, created, modified, or title
+    #[clap(long, short, default_value = "relevance")]
+    pub sort: Sort,
+    /// Reverse the sort order
+    #[clap(long, short)]
+    pub reverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sort {
+    /// Best match first
+    Relevance,
+    /// Newest first
+    Created,
+    /// Most recently modified first
+    Modified,
+    /// Alphabetical
+    Title,
+}
+
+impl Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sort::Relevance => write!(f, "relevance"),
+            Sort::Created => write!(f, "created"),
+            Sort::Modified => write!(f, "modified"),
+            Sort::Title => write!(f, "title"),
+        }
+    }
+}
+
+impl FromStr for Sort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "relevance" => Ok(Sort::Relevance),
+            "created" => Ok(Sort::Created),
+            "modified" => Ok(Sort::Modified),
+            "title" => Ok(Sort::Title),
+            _ => Err(anyhow::anyhow!(
+                "{} is not relevance, created, modified, or title",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct New {
+    pub title: String,
+    /// Tag to add to the zettel, may be repeated
+    #[clap(long = "tag", short)]
+    pub tags: Vec<String>,
+    /// Name of a template in the templates directory, without its `.md` extension
+    #[clap(long)]
+    pub template: Option<String>,
+    /// Folder inside the wiki to create the zettel in
+    #[clap(long)]
+    pub folder: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct Capture {
+    /// Path, title or file name of the zettel to append to instead of the inbox
+    #[clap(long)]
+    pub to: Option<String>,
+    /// Append to the end of this section, adding it if it is missing
+    #[clap(long)]
+    pub heading: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct Preview {
+    /// Id, path, title or file name of the zettel, or a line picked from the dmenu format
+    pub note: String,
+    /// Print the path of the zettel instead of its contents
+    #[clap(long)]
+    pub path: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct Show {
+    /// Id, path, title or file name of the zettel
+    pub note: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct Periodic {
+    /// Any day in the period, as YYYY-MM-DD. Defaults to today
+    #[clap(long)]
+    pub date: Option<NaiveDate>,
 }
 
 #[derive(Parser, Debug)]
@@ -90,4 +543,116 @@ pub struct Config {
     pub wiki_location: PathBuf,
     #[serde(rename = "zettel-dateformat")]
     pub zettel_date_format: String,
+    #[serde(default)]
+    pub ranking: Ranking,
+    #[serde(default)]
+    pub tokenizer: Tokenizer,
+    /// Directory holding note templates, `templates` inside the wiki by default
+    #[serde(rename = "templates-location")]
+    pub templates_location: Option<PathBuf>,
+    /// Default template for new zettels in each folder of the wiki
+    #[serde(rename = "folder-templates", default)]
+    pub folder_templates: BTreeMap<PathBuf, String>,
+    /// Zettel captures are appended to by default, relative to the wiki
+    #[serde(default = "default_inbox")]
+    pub inbox: PathBuf,
+    /// Named templates for `--template`
+    #[serde(rename = "output-templates", default)]
+    pub output_templates: BTreeMap<String, String>,
+    /// Links written by the markdown format
+    #[serde(rename = "markdown-links", default)]
+    pub markdown_links: LinkStyle,
+    #[serde(default)]
+    pub daily: PeriodicConfig,
+    #[serde(default)]
+    pub weekly: PeriodicConfig,
+    #[serde(default)]
+    pub monthly: PeriodicConfig,
+}
+
+/// Where periodic notes live and how they are named
+#[derive(Debug, Deserialize, Default)]
+pub struct PeriodicConfig {
+    /// Folder inside the wiki, `journal` by default
+    pub folder: Option<PathBuf>,
+    /// chrono format of the file name
+    pub format: Option<String>,
+    /// Name of a template in the templates directory
+    pub template: Option<String>,
+}
+
+fn default_inbox() -> PathBuf {
+    PathBuf::from("inbox.md")
+}
+
+impl Config {
+    pub fn templates_location(&self) -> PathBuf {
+        self.templates_location
+            .clone()
+            .unwrap_or_else(|| self.wiki_location.join("templates"))
+    }
+
+    /// Whether `path` lies in the templates folder, which is never indexed
+    pub fn is_template(&self, path: &Path) -> bool {
+        path.starts_with(self.templates_location())
+    }
+}
+
+/// Tokenizer used by the full text search table
+///
+/// Changing it rebuilds the full text index on the next run
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Tokenizer {
+    /// SQLite's default `unicode61` tokenizer
+    #[default]
+    Default,
+    /// Porter stemming, so "linking" also matches "links"
+    Porter,
+    /// Trigrams, for substring matches and CJK text
+    Trigram,
+    /// `unicode61` with diacritics removed, so "cafe" also matches "café"
+    Unicode,
+}
+
+impl Tokenizer {
+    /// Argument to FTS5's `tokenize` option, if any
+    pub fn fts5_option(&self) -> Option<&'static str> {
+        match self {
+            Tokenizer::Default => None,
+            Tokenizer::Porter => Some("porter unicode61 remove_diacritics 2"),
+            Tokenizer::Trigram => Some("trigram"),
+            Tokenizer::Unicode => Some("unicode61 remove_diacritics 2"),
+        }
+    }
+
+    /// Tokens in a full text snippet. A trigram token is about one character, so trigram
+    /// snippets take FTS5's maximum to show a similar stretch of text
+    pub fn snippet_tokens(&self) -> u32 {
+        match self {
+            Tokenizer::Trigram => 64,
+            _ => 16,
+        }
+    }
+}
+
+/// bm25 weights given to each column of the full text search table
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Ranking {
+    pub title: f64,
+    pub headers: f64,
+    pub tags: f64,
+    pub body: f64,
+}
+
+impl Default for Ranking {
+    fn default() -> Self {
+        Self {
+            title: 10.0,
+            headers: 5.0,
+            tags: 3.0,
+            body: 1.0,
+        }
+    }
 }
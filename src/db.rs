@@ -1,21 +1,218 @@
 use chrono::prelude::*;
 use rayon::prelude::*;
 use serde::Serialize;
+use sqlx::error::BoxDynError;
+use sqlx::sqlite::{Sqlite, SqliteTypeInfo, SqliteValueRef};
 use sqlx::{Executor, SqliteConnection};
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-use crate::arguments::Config;
+use crate::arguments::{Config, QueryOptions, Sort, Tokenizer};
 use crate::{HEADERS_REGEX, LINKS_REGEX, TAGS_REGEX};
 
+/// Marker FTS5 places before each matched term in a [Snippet]
+pub const MATCH_START: char = '\u{2}';
+/// Marker FTS5 places after each matched term in a [Snippet]
+pub const MATCH_END: char = '\u{3}';
+
+/// Columns selected for a [Zettel] from the zettels table aliased as `z`
+const ZETTEL_COLUMNS: &str = "z.zettel_id, z.timestamp, z.title, z.file_path, z.modified, z.words, \
+COALESCE((SELECT group_concat(tag, char(10)) FROM tags WHERE tags.zettel_id = z.zettel_id), '') AS tags, \
+COALESCE((SELECT group_concat(alias, char(10)) FROM aliases WHERE aliases.zettel_id = z.zettel_id), '') AS aliases, \
+COALESCE((SELECT group_concat(text, char(10)) FROM headers WHERE headers.zettel_id = z.zettel_id), '') AS headers, \
+(SELECT COUNT(*) FROM links WHERE links.zettel_id = z.zettel_id) AS links_out, \
+(SELECT COUNT(*) FROM links l WHERE l.target = z.zettel_id AND l.zettel_id != z.zettel_id) AS links_in";
+
 #[derive(sqlx::FromRow, Debug, Clone, Serialize)]
 pub struct Zettel {
     pub zettel_id: String,
     pub timestamp: i64,
     pub title: String,
     pub file_path: String,
+    /// Last modified timestamp of the file
+    #[sqlx(default)]
+    pub modified: i64,
+    #[sqlx(default)]
+    pub tags: TagList,
+    /// Other names for the zettel from its frontmatter
+    #[sqlx(default)]
+    pub aliases: TagList,
+    /// Text of every header, in order
+    #[sqlx(default)]
+    pub headers: TagList,
+    /// Number of words in the file
+    #[sqlx(default)]
+    pub words: i64,
+    /// Number of links in the zettel
+    #[sqlx(default)]
+    pub links_out: i64,
+    /// Number of links to the zettel from other zettels
+    #[sqlx(default)]
+    pub links_in: i64,
+    /// Excerpt of the body around a full text match
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+}
+
+impl Zettel {
+    /// Names of the fields a zettel is serialized with
+    pub const FIELDS: &'static [&'static str] = &[
+        "zettel_id",
+        "timestamp",
+        "title",
+        "file_path",
+        "modified",
+        "tags",
+        "aliases",
+        "headers",
+        "words",
+        "links_out",
+        "links_in",
+        "snippet",
+    ];
+}
+
+/// A tag in use across the wiki
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct TagSummary {
+    /// Tag without its leading `#`
+    pub tag: String,
+    /// Enclosing tag of a nested tag, `lang` for `lang/rust`
+    pub parent: Option<String>,
+    /// Number of zettels tagged with exactly this tag
+    pub count: i64,
+    /// Number of zettels tagged with this tag or one nested under it
+    pub total: i64,
+    /// Timestamp of the most recently modified zettel with this tag or one nested under it
+    pub last_used: Option<i64>,
+}
+
+/// A tag found on the same zettels as another tag
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct RelatedTag {
+    pub tag: String,
+    /// Number of zettels with both tags
+    pub count: i64,
+    /// Number of zettels with this tag
+    pub total: i64,
+    /// How much more often the tags appear together than if they were independent
+    pub lift: f64,
+}
+
+/// Everything the index knows about one zettel
+#[derive(Debug, Clone, Serialize)]
+pub struct ZettelDetails {
+    #[serde(flatten)]
+    pub zettel: Zettel,
+    /// YAML between the `---` lines at the top of the file
+    pub frontmatter: Option<String>,
+    /// Headers in the order they appear
+    pub outline: Vec<Header>,
+    /// Links in the zettel, in the order they appear
+    pub links: Vec<OutgoingLink>,
+    /// Links to the zettel from other zettels
+    pub backlinks: Vec<Backlink>,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct Header {
+    pub level: i64,
+    pub text: String,
+}
+
+/// A link found in a zettel
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct OutgoingLink {
+    /// The link as written, without the brackets of a wikilink
+    pub link: String,
+    pub label: String,
+    /// Path of the zettel linked to, `None` when the link is broken
+    pub target: Option<String>,
+}
+
+/// A link to a zettel from another zettel
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct Backlink {
+    pub zettel_id: String,
+    pub title: String,
+    pub file_path: String,
+    /// The link as written, without the brackets of a wikilink
+    pub link: String,
+    /// The line holding the link
+    #[sqlx(default)]
+    pub context: Option<String>,
+}
+
+/// Number of zettels sharing each pair of tags, keyed by tag then by tag.
+/// A tag paired with itself is the number of zettels using it.
+pub type TagMatrix = BTreeMap<String, BTreeMap<String, i64>>;
+
+/// Tags, aliases or headers of a zettel, decoded from a newline separated list
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TagList(pub Vec<String>);
+
+impl sqlx::Type<Sqlite> for TagList {
+    fn type_info() -> SqliteTypeInfo {
+        <String as sqlx::Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as sqlx::Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Sqlite> for TagList {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let tags = <String as sqlx::Decode<Sqlite>>::decode(value)?;
+        Ok(TagList(
+            tags.lines()
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+}
+
+/// Output of FTS5's `snippet()` with matched terms wrapped in [MATCH_START] and [MATCH_END]
+#[derive(sqlx::Type, Debug, Clone)]
+#[sqlx(transparent)]
+pub struct Snippet(String);
+
+impl Snippet {
+    /// Snippet on a single line with matched terms wrapped in `open` and `close`
+    pub fn marked(&self, open: &str, close: &str) -> String {
+        self.0
+            .replace(MATCH_START, open)
+            .replace(MATCH_END, close)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Snippet on a single line without any highlighting
+    pub fn plain(&self) -> String {
+        self.marked("", "")
+    }
+
+    /// Every matched term, in order
+    pub fn terms(&self) -> Vec<String> {
+        self.0
+            .split(MATCH_START)
+            .skip(1)
+            .filter_map(|rest| rest.split(MATCH_END).next())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Serialize for Snippet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.plain())
+    }
 }
 
 /// Functions for initializing and updating a Zettel Database
@@ -33,6 +230,7 @@ pub mod edit {
     ) -> Result<(), anyhow::Error> {
         let dir_entries: Vec<_> = walkdir::WalkDir::new(config.wiki_location.as_path())
             .into_iter()
+            .filter_entry(|e| !config.is_template(e.path()))
             .filter_map(|e| e.ok())
             // Implicitly filters out directory entries
             .filter(|e| e.path().extension() == Some(OsStr::new("md")))
@@ -59,6 +257,9 @@ pub mod edit {
 
     /// For any given Zettel, insert the following:
     ///
+    /// Zettels already in the database are matched by path and keep their `zettel_id`,
+    /// with their previously extracted rows replaced.
+    ///
     /// - Entire zettel into the full text search table
     /// - Extracted links into the links table
     /// - Extracted tags into the tags table
@@ -70,19 +271,47 @@ pub mod edit {
         zettels: Vec<ParserGatherer>,
     ) -> Result<(), anyhow::Error> {
         conn.execute("BEGIN").await?;
-        for zettel in zettels {
-            conn.execute(
-                sqlx::query("INSERT OR REPLACE INTO full_text VALUES(?,?);")
-                    .bind(&zettel.zettel_id)
-                    .bind(&zettel.text),
+        let mut written = Vec::new();
+        let mut names = Vec::new();
+        let mut added_tags = BTreeSet::new();
+        let mut removed_tags = BTreeSet::new();
+        for mut zettel in zettels {
+            let existing = sqlx::query_as::<_, (String,)>(
+                "SELECT zettel_id FROM zettels WHERE file_path = ?;",
             )
+            .bind(zettel.path.to_str())
+            .fetch_optional(&mut *conn)
             .await?;
+            if let Some((zettel_id,)) = existing {
+                removed_tags.extend(tags_of(conn, &zettel_id).await?);
+                for table in &["full_text", "headers", "tags", "links", "aliases"] {
+                    conn.execute(
+                        sqlx::query(&format!("DELETE FROM {} WHERE zettel_id = ?;", table))
+                            .bind(&zettel_id),
+                    )
+                    .await?;
+                }
+                zettel.zettel_id = zettel_id;
+            }
+            written.push(zettel.zettel_id.clone());
+            names.extend(
+                zettel
+                    .path
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .map(str::to_string),
+            );
+            added_tags.extend(zettel.tags.iter().cloned());
+            insert_full_text(conn, &zettel).await?;
             conn.execute(
-                sqlx::query("INSERT OR REPLACE INTO zettels VALUES(?,?,?,?);")
+                sqlx::query("INSERT OR REPLACE INTO zettels (zettel_id, timestamp, title, file_path, modified, name, words) VALUES(?,?,?,?,?,?,?);")
                     .bind(&zettel.zettel_id)
-                    .bind(&zettel.timestamp)
+                    .bind(zettel.timestamp)
                     .bind(&zettel.title)
-                    .bind(&zettel.path.to_str()),
+                    .bind(zettel.path.to_str())
+                    .bind(zettel.modified)
+                    .bind(zettel.path.file_stem().and_then(OsStr::to_str))
+                    .bind(zettel.words),
             )
             .await?;
             for (level, text) in zettel.headers {
@@ -102,20 +331,189 @@ pub mod edit {
                 )
                 .await?;
             }
-            for (label, link) in zettel.links {
+            for alias in zettel.aliases {
                 conn.execute(
-                    sqlx::query("INSERT OR REPLACE INTO links VALUES(?,?,?);")
+                    sqlx::query("INSERT OR REPLACE INTO aliases VALUES(?,?);")
                         .bind(&zettel.zettel_id)
-                        .bind(label)
-                        .bind(link),
+                        .bind(alias),
+                )
+                .await?;
+            }
+            for (label, link, name) in zettel.links {
+                conn.execute(
+                    sqlx::query(
+                        "INSERT OR REPLACE INTO links (zettel_id, link, label, name) VALUES(?,?,?,?);",
+                    )
+                    .bind(&zettel.zettel_id)
+                    .bind(label)
+                    .bind(link)
+                    .bind(name),
                 )
                 .await?;
             }
         }
+        resolve_links(conn, &written, &names).await?;
+        sync_tag_hierarchy(conn, &added_tags, &removed_tags).await?;
         conn.execute("COMMIT").await?;
         Ok(())
     }
 
+    /// Tags a zettel has in the database
+    async fn tags_of(
+        conn: &mut SqliteConnection,
+        zettel_id: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let tags = sqlx::query_as::<_, (String,)>("SELECT tag FROM tags WHERE zettel_id = ?;")
+            .bind(zettel_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(tags.into_iter().map(|(tag,)| tag).collect())
+    }
+
+    /// Point links at the zettel whose file name they name: the links of the zettels `written`,
+    /// and links anywhere naming one of `names`, which may have been found before the zettel
+    /// they link to was indexed, or point at one that is gone
+    async fn resolve_links(
+        conn: &mut SqliteConnection,
+        written: &[String],
+        names: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let update = "UPDATE links SET target = (SELECT zettel_id FROM zettels WHERE zettels.name = links.name COLLATE NOCASE ORDER BY zettel_id LIMIT 1)";
+        for zettel_id in written {
+            conn.execute(sqlx::query(&format!("{} WHERE zettel_id = ?;", update)).bind(zettel_id))
+                .await?;
+        }
+        for name in names {
+            conn.execute(
+                sqlx::query(&format!("{} WHERE name = ? COLLATE NOCASE;", update)).bind(name),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Keep the tag_hierarchy table in step with the tags in use. `added` tags are recorded
+    /// along with their parents, so that `lang/rust` records `lang` as its parent and `lang`
+    /// is recorded as a root tag, and `removed` tags and their parents are dropped once no
+    /// zettel uses them or a tag nested under them
+    async fn sync_tag_hierarchy(
+        conn: &mut SqliteConnection,
+        added: &BTreeSet<String>,
+        removed: &BTreeSet<String>,
+    ) -> Result<(), anyhow::Error> {
+        let lineage = |tags: &BTreeSet<String>| {
+            tags.iter()
+                .flat_map(|tag| {
+                    std::iter::successors(Some(tag.as_str()), |tag| {
+                        tag.rsplit_once('/').map(|(parent, _)| parent)
+                    })
+                })
+                .map(str::to_string)
+                .collect::<BTreeSet<_>>()
+        };
+        for tag in lineage(added) {
+            let parent = tag.rsplit_once('/').map(|(parent, _)| parent.to_string());
+            conn.execute(
+                sqlx::query("INSERT OR IGNORE INTO tag_hierarchy VALUES(?,?);")
+                    .bind(tag)
+                    .bind(parent),
+            )
+            .await?;
+        }
+        for tag in lineage(removed) {
+            conn.execute(
+                sqlx::query(
+                    "DELETE FROM tag_hierarchy WHERE tag = ?1 AND NOT EXISTS
+(SELECT 1 FROM tags WHERE tag = ?1 COLLATE NOCASE OR (tag > ?1 || '/' COLLATE NOCASE AND tag < ?1 || '0' COLLATE NOCASE));",
+                )
+                .bind(tag),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Index the title, headers, tags and body of a zettel as separate full text search columns
+    async fn insert_full_text(
+        conn: &mut SqliteConnection,
+        zettel: &ParserGatherer,
+    ) -> Result<(), anyhow::Error> {
+        let headers = zettel
+            .headers
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        conn.execute(
+            sqlx::query("INSERT OR REPLACE INTO full_text VALUES(?,?,?,?,?);")
+                .bind(&zettel.zettel_id)
+                .bind(&zettel.title)
+                .bind(headers)
+                .bind(zettel.tags.join(" "))
+                .bind(&zettel.text),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Recreate the full text search table from the files already in the database
+    /// when its schema or tokenizer no longer matches [full_text_schema]
+    async fn sync_full_text(
+        conn: &mut SqliteConnection,
+        config: &Config,
+    ) -> Result<(), anyhow::Error> {
+        let expected = full_text_schema(config.tokenizer);
+        let current = sqlx::query_as::<_, (String,)>(
+            "SELECT sql FROM sqlite_master WHERE name = 'full_text';",
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some((sql,)) = current {
+            if sql.trim_end_matches(';') == expected {
+                return Ok(());
+            }
+        }
+        let zettels =
+            sqlx::query_as::<_, (String, String)>("SELECT zettel_id, file_path FROM zettels;")
+                .fetch_all(&mut *conn)
+                .await?;
+        let zettels = zettels
+            .into_par_iter()
+            .filter_map(|(zettel_id, file_path)| {
+                let path = PathBuf::from(file_path);
+                let metadata = fs::metadata(&path).ok()?;
+                let mut gathered = gather_info(path, metadata).ok()?;
+                gathered.zettel_id = zettel_id;
+                Some(gathered)
+            })
+            .collect::<Vec<_>>();
+        conn.execute("BEGIN").await?;
+        conn.execute("DROP TABLE IF EXISTS full_text;").await?;
+        conn.execute(expected.as_str()).await?;
+        for zettel in zettels {
+            insert_full_text(conn, &zettel).await?;
+        }
+        conn.execute("COMMIT").await?;
+        Ok(())
+    }
+
+    /// `CREATE` statement for the full text search table
+    fn full_text_schema(tokenizer: Tokenizer) -> String {
+        let columns = "zettel_id UNINDEXED, title, headers, tags, body";
+        match tokenizer.fts5_option() {
+            Some(tokenize) => format!(
+                "CREATE VIRTUAL TABLE full_text USING FTS5
+({}, tokenize = '{}')",
+                columns, tokenize
+            ),
+            None => format!(
+                "CREATE VIRTUAL TABLE full_text USING FTS5
+({})",
+                columns
+            ),
+        }
+    }
+
     /// Incredibly similar to [fill_db](crate::db::initialize::fill_db) except that it operates on a received list of Paths rather than walking the config path for markdown files
     pub async fn fill_n(
         conn: &mut SqliteConnection,
@@ -137,31 +535,41 @@ pub mod edit {
     /// - list of tags
     /// - list of links
     /// - list of headers
+    /// - list of aliases from the frontmatter
+    /// - number of words
     fn gather_info(path: PathBuf, metadata: Metadata) -> Result<ParserGatherer, anyhow::Error> {
         let timestamp: chrono::DateTime<Utc> = metadata.created()?.into();
         let timestamp = timestamp.timestamp();
+        let modified: chrono::DateTime<Utc> = metadata.modified()?.into();
+        let modified = modified.timestamp();
         let mut content = String::new();
         let mut file = File::open(&path)?;
         file.read_to_string(&mut content)?;
         let zettel_id = Uuid::new_v4().to_string();
-        let tags = TAGS_REGEX
-            .captures_iter(&content)
-            .filter_map(|v| v.get(0))
-            .map(|v| v.as_str().to_string())
-            .collect::<Vec<_>>();
+        let mut tags = text_tags(&content);
+        tags.extend(
+            frontmatter_list(&content, "tags")
+                .iter()
+                .map(|tag| normalize_tag(tag)),
+        );
+        tags.retain(|tag| !tag.is_empty());
+        tags.sort();
+        tags.dedup();
         let links = LINKS_REGEX
             .captures_iter(&content)
             .filter(|v| v.get(0).is_some())
             .map(|v| {
-                if v.get(4).is_some() {
+                if let Some(wikilink) = v.get(4) {
                     (
-                        v.get(4).unwrap().as_str().to_string(),
-                        v.get(4).unwrap().as_str().to_string(),
+                        wikilink.as_str().to_string(),
+                        wikilink.as_str().to_string(),
+                        wikilink_name(wikilink.as_str()),
                     )
                 } else {
                     (
                         v.get(1).unwrap().as_str().to_string(),
                         v.get(2).unwrap().as_str().to_string(),
+                        v.get(3).and_then(|path| markdown_link_name(path.as_str())),
                     )
                 }
             })
@@ -176,37 +584,175 @@ pub mod edit {
             .find(|h| h.0 == 1)
             .map(|v| v.1.clone())
             .unwrap_or_else(|| path.to_str().unwrap().to_string());
+        let aliases = frontmatter_list(&content, "aliases");
+        let words = content.split_whitespace().count() as i64;
         Ok(ParserGatherer {
+            words,
             text: content,
             path,
             timestamp,
+            modified,
             zettel_id,
             headers,
             tags,
             links,
             title,
+            aliases,
+        })
+    }
+
+    /// Inline `#tags` of `text`, without their `#`. Like Obsidian and Zettlr, a tag needs at least
+    /// one letter, so `#1` and issue references are skipped
+    pub fn inline_tags(text: &str) -> impl Iterator<Item = regex::Match<'_>> {
+        TAGS_REGEX
+            .captures_iter(text)
+            .filter_map(|v| v.get(1))
+            .filter(|v| v.as_str().chars().any(char::is_alphabetic))
+    }
+
+    /// File name a `[[name]]`, `[[folder/name]]`, `[[name|label]]` or `[[name#heading]]` links to
+    fn wikilink_name(link: &str) -> Option<String> {
+        let link = link.split(['|', '#']).next()?;
+        let name = link.rsplit('/').next()?.trim();
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    /// File name, without `.md`, a `[label](folder/name.md)` links to. `None` for anything but a markdown file
+    fn markdown_link_name(path: &str) -> Option<String> {
+        let name = path.rsplit(['/', '(']).next()?.strip_suffix(".md")?;
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    /// Inline `#tags` of a zettel, leaving out its frontmatter, fenced code blocks and code spans
+    pub fn text_tags(content: &str) -> Vec<String> {
+        line_kinds(content)
+            .filter(|(kind, _)| *kind == LineKind::Text)
+            .flat_map(|(_, line)| line.split('`').step_by(2))
+            .flat_map(inline_tags)
+            .map(|tag| normalize_tag(tag.as_str()))
+            .collect()
+    }
+
+    /// What a line of a zettel holds, to tell text apart from frontmatter and code
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum LineKind {
+        /// Between `---` lines at the very start of the zettel, those lines included
+        Frontmatter,
+        /// Inside a block fenced by ``` or `~~~`, the fences included
+        Code,
+        Text,
+    }
+
+    /// Every line of `content`, split on `\n`, with what it holds
+    pub fn line_kinds(content: &str) -> impl Iterator<Item = (LineKind, &str)> {
+        let mut in_frontmatter = false;
+        let mut fence: Option<&str> = None;
+        content.split('\n').enumerate().map(move |(number, line)| {
+            let trimmed = line.trim();
+            let kind = if number == 0 && trimmed == "---" {
+                in_frontmatter = true;
+                LineKind::Frontmatter
+            } else if in_frontmatter {
+                in_frontmatter = trimmed != "---";
+                LineKind::Frontmatter
+            } else if let Some(marker) = fence {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+                LineKind::Code
+            } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fence = Some(&trimmed[..3]);
+                LineKind::Code
+            } else {
+                LineKind::Text
+            };
+            (kind, line)
         })
     }
 
-    pub async fn initialize_db(conn: &mut SqliteConnection) -> Result<(), anyhow::Error> {
+    /// A tag as stored in the tags table: without the leading `#` or trailing punctuation
+    pub fn normalize_tag(tag: &str) -> String {
+        tag.trim_start_matches('#')
+            .trim_end_matches(['.', '/'])
+            .to_string()
+    }
+
+    /// YAML frontmatter of a zettel, between `---` lines at its very start
+    pub fn frontmatter(content: &str) -> Option<&str> {
+        let rest = content
+            .strip_prefix("---\n")
+            .or_else(|| content.strip_prefix("---\r\n"))?;
+        let mut end = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == "---" {
+                return Some(rest[..end].trim_end());
+            }
+            end += line.len();
+        }
+        None
+    }
+
+    /// Values of `key` in a zettel's YAML frontmatter, written either inline (`key: [a, b]`)
+    /// or as a block list (`- a` on the following lines)
+    fn frontmatter_list(content: &str, key: &str) -> Vec<String> {
+        let mut lines = content.lines();
+        if lines.next().map(str::trim_end) != Some("---") {
+            return Vec::new();
+        }
+        let mut values = Vec::new();
+        let mut in_key = false;
+        for line in lines.take_while(|line| line.trim_end() != "---") {
+            let trimmed = line.trim();
+            if in_key && trimmed.starts_with('-') {
+                values.push(unquote(trimmed.trim_start_matches('-')));
+                continue;
+            }
+            in_key = false;
+            if let Some(rest) = line.strip_prefix(key).and_then(|r| r.strip_prefix(':')) {
+                let rest = rest.trim();
+                if rest.is_empty() {
+                    in_key = true;
+                } else if rest.starts_with('[') && rest.ends_with(']') {
+                    values.extend(rest[1..rest.len() - 1].split(',').map(unquote));
+                } else {
+                    values.push(unquote(rest));
+                }
+            }
+        }
+        values.retain(|v| !v.is_empty());
+        values
+    }
+
+    /// A frontmatter value without surrounding whitespace and quotes
+    pub fn unquote(value: &str) -> String {
+        value
+            .trim()
+            .trim_matches(|c: char| c == '"' || c == '\'')
+            .to_string()
+    }
+
+    pub async fn initialize_db(
+        conn: &mut SqliteConnection,
+        config: &Config,
+    ) -> Result<(), anyhow::Error> {
         conn.execute("BEGIN").await?;
         conn.execute(
             "PRAGMA foreign_keys=on;
 PRAGMA WAL=on;",
         )
         .await?;
-        conn.execute(
-            "CREATE VIRTUAL TABLE full_text USING FTS5
-(zettel_id, body);",
-        )
-        .await?;
+        conn.execute(full_text_schema(config.tokenizer).as_str())
+            .await?;
         conn.execute(
             "CREATE TABLE zettels
 (
     zettel_id TEXT UNIQUE PRIMARY KEY,
     timestamp INTEGER,
     title TEXT,
-    file_path TEXT NOT NULL
+    file_path TEXT NOT NULL,
+    modified INTEGER NOT NULL DEFAULT 0,
+    name TEXT,
+    words INTEGER NOT NULL DEFAULT 0
 );",
         )
         .await?;
@@ -235,26 +781,138 @@ PRAGMA WAL=on;",
     zettel_id TEXT NOT NULL,
     link TEXT,
     label TEXT,
+    name TEXT,
+    target TEXT,
     FOREIGN KEY (zettel_id) REFERENCES zettels(zettel_id)
 );",
         )
         .await?;
+        migrate(conn).await?;
+        conn.execute(format!("PRAGMA user_version = {};", SCHEMA_VERSION).as_str())
+            .await?;
         conn.execute("COMMIT").await?;
         Ok(())
     }
 
+    /// Bring a database made by an older version up to date: rebuild the full text table if
+    /// its tokenizer changed, and when `user_version` is behind [SCHEMA_VERSION], migrate it
+    /// and read every zettel again to fill in what the migration added.
+    ///
+    /// `user_version` is only set once the zettels have been read, so an interrupted upgrade
+    /// starts over the next time
+    pub async fn upgrade(
+        conn: &mut SqliteConnection,
+        config: &Config,
+    ) -> Result<(), anyhow::Error> {
+        let (version,) = sqlx::query_as::<_, (i64,)>("PRAGMA user_version;")
+            .fetch_one(&mut *conn)
+            .await?;
+        if version < SCHEMA_VERSION {
+            conn.execute("BEGIN").await?;
+            migrate(conn).await?;
+            conn.execute("COMMIT").await?;
+        }
+        sync_full_text(conn, config).await?;
+        if version < SCHEMA_VERSION {
+            fill_db(conn, config, None).await?;
+            conn.execute(format!("PRAGMA user_version = {};", SCHEMA_VERSION).as_str())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// `user_version` of a database with everything [migrate] creates, and its zettels
+    /// read the way this version reads them
+    const SCHEMA_VERSION: i64 = 2;
+
+    /// Create tables, columns and indexes added since a database was first initialized
+    async fn migrate(conn: &mut SqliteConnection) -> Result<(), anyhow::Error> {
+        let columns_of = |table: &'static str| {
+            sqlx::query_as::<_, (String,)>("SELECT name FROM pragma_table_info(?);").bind(table)
+        };
+        let columns = columns_of("zettels").fetch_all(&mut *conn).await?;
+        let link_columns = columns_of("links").fetch_all(&mut *conn).await?;
+        let has_column = |column: &str| columns.iter().any(|(name,)| name == column);
+        if !has_column("modified") {
+            conn.execute("ALTER TABLE zettels ADD COLUMN modified INTEGER NOT NULL DEFAULT 0;")
+                .await?;
+        }
+        if !has_column("name") {
+            conn.execute("ALTER TABLE zettels ADD COLUMN name TEXT;")
+                .await?;
+        }
+        if !has_column("words") {
+            conn.execute("ALTER TABLE zettels ADD COLUMN words INTEGER NOT NULL DEFAULT 0;")
+                .await?;
+        }
+        if !link_columns.iter().any(|(name,)| name == "target") {
+            conn.execute("ALTER TABLE links ADD COLUMN name TEXT;")
+                .await?;
+            conn.execute("ALTER TABLE links ADD COLUMN target TEXT;")
+                .await?;
+        }
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS aliases
+(
+    zettel_id TEXT NOT NULL,
+    alias TEXT,
+    FOREIGN KEY (zettel_id) REFERENCES zettels(zettel_id)
+);",
+        )
+        .await?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS zettels_name ON zettels (name COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS links_zettel_id ON links (zettel_id);
+CREATE INDEX IF NOT EXISTS links_target ON links (target);
+CREATE INDEX IF NOT EXISTS links_name ON links (name COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS tags_zettel_id ON tags (zettel_id);
+CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS headers_zettel_id ON headers (zettel_id);
+CREATE INDEX IF NOT EXISTS aliases_zettel_id ON aliases (zettel_id);",
+        )
+        .await?;
+        // Tags used to be stored with their leading `#`
+        conn.execute("UPDATE tags SET tag = substr(tag, 2) WHERE tag LIKE '#%';")
+            .await?;
+        let hierarchy_exists = sqlx::query_as::<_, (String,)>(
+            "SELECT name FROM sqlite_master WHERE name = 'tag_hierarchy';",
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .is_some();
+        if !hierarchy_exists {
+            conn.execute(
+                "CREATE TABLE tag_hierarchy
+(
+    tag TEXT PRIMARY KEY COLLATE NOCASE,
+    parent TEXT COLLATE NOCASE
+);",
+            )
+            .await?;
+            let tags = sqlx::query_as::<_, (String,)>("SELECT DISTINCT tag FROM tags;")
+                .fetch_all(&mut *conn)
+                .await?;
+            let tags = tags.into_iter().map(|(tag,)| tag).collect();
+            sync_tag_hierarchy(conn, &tags, &BTreeSet::new()).await?;
+        }
+        Ok(())
+    }
+
     #[derive(Debug, Clone, Default)]
     pub struct ParserGatherer {
         title: String,
         text: String,
         path: PathBuf,
         timestamp: i64,
+        modified: i64,
+        words: i64,
         zettel_id: String,
         /// (Header Level, Text)
         headers: Vec<(i32, String)>,
-        /// (Label, URL)
-        links: Vec<(String, String)>,
+        /// (Label, URL, file name of the zettel linked to)
+        links: Vec<(String, String, Option<String>)>,
         tags: Vec<String>,
+        aliases: Vec<String>,
     }
 
     /// Update a zettel when notified of a file name change
@@ -277,7 +935,7 @@ PRAGMA WAL=on;",
             sqlx::query_as::<_, Zettel>(
                 "UPDATE zettels SET timestamp = ?, title = ? WHERE zettel_id = ?",
             )
-            .bind(&new_timestamp)
+            .bind(new_timestamp)
             .bind(new_title)
             .bind(old_title)
             .bind(&id),
@@ -285,7 +943,7 @@ PRAGMA WAL=on;",
         .await?;
         conn.execute(
             sqlx::query_as::<_, Zettel>("UPDATE full_text SET timestamp = ? WHERE zettel_id = ?")
-                .bind(&new_timestamp)
+                .bind(new_timestamp)
                 .bind(old_title)
                 .bind(&id),
         )
@@ -294,7 +952,7 @@ PRAGMA WAL=on;",
             sqlx::query_as::<_, Zettel>(
                 "UPDATE headers SET timestamp = ?, title = ? zettel_id = ?",
             )
-            .bind(&new_timestamp)
+            .bind(new_timestamp)
             .bind(new_title)
             .bind(&id),
         )
@@ -303,7 +961,7 @@ PRAGMA WAL=on;",
             sqlx::query_as::<_, Zettel>(
                 "UPDATE links SET timestamp = ?, title = ? WHERE zettel_id = ?",
             )
-            .bind(&new_timestamp)
+            .bind(new_timestamp)
             .bind(new_title)
             .bind(old_title)
             .bind(&id),
@@ -313,7 +971,7 @@ PRAGMA WAL=on;",
             sqlx::query_as::<_, Zettel>(
                 "UPDATE tags SET timestamp = ?, title = ? WHERE zettel_id = ?",
             )
-            .bind(&new_timestamp)
+            .bind(new_timestamp)
             .bind(new_title)
             .bind(&id),
         )
@@ -321,88 +979,421 @@ PRAGMA WAL=on;",
         Ok(())
     }
 
-    /// Update a zettel when notified of a file name change
+    /// Drop a zettel and everything indexed from it when its file is removed
     pub async fn remove(conn: &mut SqliteConnection, old: &Path) -> Result<(), anyhow::Error> {
-        let id = query::get_by_path(conn, old.to_str().unwrap())
-            .await?
-            .zettel_id;
-        let old_title = old.file_name().unwrap().to_str();
-        conn.execute(
-            sqlx::query_as::<_, Zettel>("DROP FROM TABLE zettels WHERE zettel_id = ? ")
-                .bind(old_title)
-                .bind(&id),
-        )
-        .await?;
-        conn.execute(
-            sqlx::query_as::<_, Zettel>("DROP FROM TABLE headers WHERE zettel_id = ?")
-                .bind(old_title)
-                .bind(&id),
-        )
-        .await?;
-        conn.execute(
-            sqlx::query_as::<_, Zettel>("DROP FROM TABLE links WHERE zettel_id = ?")
-                .bind(old_title)
-                .bind(&id),
-        )
-        .await?;
-        conn.execute(
-            sqlx::query_as::<_, Zettel>("DROP FROM TABLE tags WHERE zettel_id = ?")
-                .bind(old_title)
-                .bind(&id),
-        )
-        .await?;
-        conn.execute(
-            sqlx::query_as::<_, Zettel>("DROP FROM TABLE full_text WHERE zettel_id = ?")
-                .bind(old_title),
+        let existing = sqlx::query_as::<_, (String, Option<String>)>(
+            "SELECT zettel_id, name FROM zettels WHERE file_path = ?;",
         )
+        .bind(old.to_str())
+        .fetch_optional(&mut *conn)
         .await?;
+        let (zettel_id, name) = match existing {
+            Some(existing) => existing,
+            None => return Ok(()),
+        };
+        conn.execute("BEGIN").await?;
+        let tags = tags_of(conn, &zettel_id).await?.into_iter().collect();
+        for table in &[
+            "full_text",
+            "headers",
+            "tags",
+            "links",
+            "aliases",
+            "zettels",
+        ] {
+            conn.execute(
+                sqlx::query(&format!("DELETE FROM {} WHERE zettel_id = ?;", table))
+                    .bind(&zettel_id),
+            )
+            .await?;
+        }
+        resolve_links(conn, &[], name.as_slice()).await?;
+        sync_tag_hierarchy(conn, &BTreeSet::new(), &tags).await?;
+        conn.execute("COMMIT").await?;
         Ok(())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn reads_unicode_tags() {
+            assert_eq!(
+                text_tags("#café and #日本語, (see #lang/rust.)"),
+                ["café", "日本語", "lang/rust"]
+            );
+        }
+
+        #[test]
+        fn skips_numeric_tags() {
+            assert_eq!(text_tags("issue #1 and #2024 but #y2k"), ["y2k"]);
+        }
+
+        #[test]
+        fn skips_code() {
+            let content = "#text\n```\n#fenced\n```\n~~~\n#tilde\n~~~\n`#span` and #after";
+            assert_eq!(text_tags(content), ["text", "after"]);
+        }
+
+        #[test]
+        fn skips_frontmatter() {
+            let content = "---\ntitle: about #draft\n---\n#text";
+            assert_eq!(text_tags(content), ["text"]);
+        }
+
+        #[test]
+        fn skips_heading_links() {
+            assert_eq!(
+                text_tags("[intro](#intro), [[note#heading]] and [#tag]"),
+                ["tag"]
+            );
+        }
+    }
 }
 
 /// Functions for querying a Zettel Database
 pub mod query {
     use super::*;
-    /// Search full text search table for zettels matching `text`
+    use crate::fuzzy;
+    use futures_util::TryStreamExt;
+    use sqlx::query::QueryAs;
+    use sqlx::sqlite::SqliteArguments;
+    use std::cmp::Reverse;
+    use std::fs;
+    use std::path::Path;
+    /// Search full text search table for zettels matching `text`, ranked by the configured column weights
     pub async fn fulltext(
         conn: &mut SqliteConnection,
         text: &str,
-    ) -> Result<Vec<Zettel>, anyhow::Error> {
-        Ok(sqlx::query_as::<_, Zettel>("SELECT z.zettel_id, title, timestamp, file_path FROM full_text ft JOIN zettels z ON z.zettel_id = ft.zettel_id WHERE full_text MATCH ? ORDER BY rank;")
-            .bind(text)
-            .fetch_all( conn).await?)
+        config: &Config,
+        options: &QueryOptions,
+        each: impl FnMut(Zettel) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let ranking = &config.ranking;
+        let sql = format!("SELECT {}, snippet(full_text, 4, ?, ?, '…', {}) AS snippet, bm25(full_text, 0.0, ?, ?, ?, ?) AS relevance FROM full_text ft JOIN zettels z ON z.zettel_id = ft.zettel_id WHERE full_text MATCH ? {};", ZETTEL_COLUMNS, config.tokenizer.snippet_tokens(), order_and_page(options, "relevance"));
+        let query = sqlx::query_as::<_, Zettel>(&sql)
+            .bind(MATCH_START.to_string())
+            .bind(MATCH_END.to_string())
+            .bind(ranking.title)
+            .bind(ranking.headers)
+            .bind(ranking.tags)
+            .bind(ranking.body)
+            .bind(text);
+        stream(conn, query, each).await
     }
 
-    /// Search links table for zettels matching `text`
+    /// Search links table for zettels matching `text`, zettels with the most matching links first
     pub async fn links(
         conn: &mut SqliteConnection,
         text: &str,
-    ) -> Result<Vec<Zettel>, anyhow::Error> {
-        Ok(sqlx::query_as::<_, Zettel>("SELECT DISTINCT z.zettel_id, title, timestamp, file_path FROM links l JOIN zettels z ON z.zettel_id = l.zettel_id WHERE link LIKE ?;")
-            .bind(format!("%{}%",text))
-            .fetch_all( conn).await?)
+        options: &QueryOptions,
+        each: impl FnMut(Zettel) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let sql = format!("SELECT {} FROM links l JOIN zettels z ON z.zettel_id = l.zettel_id WHERE link LIKE ? GROUP BY z.zettel_id {};", ZETTEL_COLUMNS, order_and_page(options, "-COUNT(*)"));
+        let query = sqlx::query_as::<_, Zettel>(&sql).bind(format!("%{}%", text));
+        stream(conn, query, each).await
     }
 
-    /// Search tags table for zettels matching `text`
+    /// Search tags table for zettels tagged `text` or any of its nested tags (`lang` matches `lang/rust`),
+    /// or with any tag starting with `text` when `prefix` is set.
+    /// Zettels with the most matching tags come first.
     pub async fn tags(
         conn: &mut SqliteConnection,
         text: &str,
-    ) -> Result<Vec<Zettel>, anyhow::Error> {
-        Ok(sqlx::query_as::<_, Zettel>("SELECT z.zettel_id, title, timestamp, file_path FROM tags t JOIN zettels z ON z.zettel_id = t.zettel_id WHERE tag LIKE ?;")
-            .bind(format!("%{}%",text))
-            .fetch_all( conn).await?)
+        prefix: bool,
+        options: &QueryOptions,
+        each: impl FnMut(Zettel) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let tag = like_escape(&edit::normalize_tag(text));
+        let condition = if prefix {
+            "tag LIKE ? || '%' ESCAPE '\\'"
+        } else {
+            "(tag LIKE ? ESCAPE '\\' OR tag LIKE ? || '/%' ESCAPE '\\')"
+        };
+        let sql = format!("SELECT {} FROM tags t JOIN zettels z ON z.zettel_id = t.zettel_id WHERE {} GROUP BY z.zettel_id {};", ZETTEL_COLUMNS, condition, order_and_page(options, "-COUNT(*)"));
+        let mut query = sqlx::query_as::<_, Zettel>(&sql).bind(&tag);
+        if !prefix {
+            query = query.bind(&tag);
+        }
+        stream(conn, query, each).await
+    }
+
+    /// Hand each zettel `query` finds to `each` as soon as SQLite produces it
+    async fn stream<'q>(
+        conn: &mut SqliteConnection,
+        query: QueryAs<'q, Sqlite, Zettel, SqliteArguments<'q>>,
+        mut each: impl FnMut(Zettel) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let mut rows = query.fetch(conn);
+        while let Some(zettel) = rows.try_next().await? {
+            each(zettel)?;
+        }
+        Ok(())
+    }
+
+    /// Everything the index knows about `zettel`, with the frontmatter read from its file and
+    /// each backlink given the line it is on
+    pub async fn details(
+        conn: &mut SqliteConnection,
+        zettel: Zettel,
+    ) -> Result<ZettelDetails, anyhow::Error> {
+        let outline = sqlx::query_as::<_, Header>(
+            "SELECT level, text FROM headers WHERE zettel_id = ? ORDER BY rowid;",
+        )
+        .bind(&zettel.zettel_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let links = sqlx::query_as::<_, OutgoingLink>(
+            "SELECT l.link, l.label, z.file_path AS target FROM links l LEFT JOIN zettels z ON z.zettel_id = l.target WHERE l.zettel_id = ? ORDER BY l.rowid;",
+        )
+        .bind(&zettel.zettel_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut backlinks = sqlx::query_as::<_, Backlink>(
+            "SELECT src.zettel_id, src.title, src.file_path, l.link FROM links l JOIN zettels src ON src.zettel_id = l.zettel_id \
+WHERE l.target = ?1 AND l.zettel_id != ?1 ORDER BY src.title COLLATE NOCASE, l.rowid;",
+        )
+        .bind(&zettel.zettel_id)
+        .fetch_all(conn)
+        .await?;
+        for backlink in &mut backlinks {
+            let wikilink = format!("[[{}", backlink.link);
+            backlink.context = fs::read_to_string(&backlink.file_path)
+                .ok()
+                .and_then(|content| {
+                    let line = content
+                        .lines()
+                        .find(|line| line.contains(&wikilink))
+                        .or_else(|| content.lines().find(|line| line.contains(&backlink.link)))?;
+                    Some(line.trim().to_string())
+                });
+        }
+        let frontmatter = fs::read_to_string(&zettel.file_path)
+            .ok()
+            .and_then(|content| edit::frontmatter(&content).map(str::to_string));
+        Ok(ZettelDetails {
+            zettel,
+            frontmatter,
+            outline,
+            links,
+            backlinks,
+        })
+    }
+
+    /// Escape the wildcards of a `LIKE` pattern, for use with `ESCAPE '\'`
+    fn like_escape(text: &str) -> String {
+        text.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    }
+
+    /// `ORDER BY`, `LIMIT` and `OFFSET` clauses for `options`,
+    /// where `relevance` is an expression that sorts the best match first
+    fn order_and_page(options: &QueryOptions, relevance: &str) -> String {
+        let (order, descending) = match options.sort {
+            Sort::Relevance => (relevance, false),
+            Sort::Created => ("z.timestamp", true),
+            Sort::Modified => ("z.modified", true),
+            Sort::Title => ("z.title COLLATE NOCASE", false),
+        };
+        let direction = if descending != options.reverse {
+            "DESC"
+        } else {
+            "ASC"
+        };
+        format!(
+            "ORDER BY {} {} LIMIT {} OFFSET {}",
+            order,
+            direction,
+            options.limit.map(i64::from).unwrap_or(-1),
+            options.offset
+        )
+    }
+
+    /// Apply `options` to zettels already ordered by relevance
+    fn sort_and_page(mut zettels: Vec<Zettel>, options: &QueryOptions) -> Vec<Zettel> {
+        match options.sort {
+            Sort::Relevance => {}
+            Sort::Created => zettels.sort_by_key(|zettel| Reverse(zettel.timestamp)),
+            Sort::Modified => zettels.sort_by_key(|zettel| Reverse(zettel.modified)),
+            Sort::Title => zettels.sort_by_key(|zettel| zettel.title.to_lowercase()),
+        }
+        page(zettels, options)
+    }
+
+    /// Apply the `reverse`, `offset` and `limit` of `options` to results already sorted
+    fn page<T>(mut items: Vec<T>, options: &QueryOptions) -> Vec<T> {
+        if options.reverse {
+            items.reverse();
+        }
+        items
+            .into_iter()
+            .skip(options.offset as usize)
+            .take(
+                options
+                    .limit
+                    .map(|limit| limit as usize)
+                    .unwrap_or(usize::MAX),
+            )
+            .collect()
+    }
+
+    /// Fuzzy match `text` against the titles, aliases and file names of every zettel,
+    /// best matches first and more recent zettels breaking ties
+    pub async fn find(
+        conn: &mut SqliteConnection,
+        text: &str,
+        options: &QueryOptions,
+        each: impl FnMut(Zettel) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let zettels =
+            sqlx::query_as::<_, Zettel>(&format!("SELECT {} FROM zettels z;", ZETTEL_COLUMNS))
+                .fetch_all(conn)
+                .await?;
+        let mut scored = zettels
+            .into_iter()
+            .filter_map(|zettel| {
+                let file_name = Path::new(&zettel.file_path)
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or_default();
+                let best = std::iter::once(zettel.title.as_str())
+                    .chain(std::iter::once(file_name))
+                    .chain(zettel.aliases.0.iter().map(String::as_str))
+                    .filter_map(|candidate| fuzzy::score(text, candidate))
+                    .max()?;
+                Some((best, zettel))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(a, a_zettel), (b, b_zettel)| {
+            b.cmp(a).then(b_zettel.timestamp.cmp(&a_zettel.timestamp))
+        });
+        let zettels = scored.into_iter().map(|(_, zettel)| zettel).collect();
+        sort_and_page(zettels, options)
+            .into_iter()
+            .try_for_each(each)
+    }
+
+    /// Tags appearing on the same zettels as `tag` (or a tag nested under it),
+    /// those sharing the most zettels first
+    pub async fn related_tags(
+        conn: &mut SqliteConnection,
+        tag: &str,
+        options: &QueryOptions,
+    ) -> Result<Vec<RelatedTag>, anyhow::Error> {
+        let tag = like_escape(&edit::normalize_tag(tag));
+        let sql = "WITH target AS (SELECT DISTINCT zettel_id FROM tags WHERE tag LIKE ?1 ESCAPE '\\' OR tag LIKE ?1 || '/%' ESCAPE '\\'), totals AS (SELECT tag, COUNT(DISTINCT zettel_id) AS total FROM tags GROUP BY tag) SELECT t.tag, COUNT(DISTINCT t.zettel_id) AS count, totals.total, COUNT(DISTINCT t.zettel_id) * 1.0 * (SELECT COUNT(*) FROM zettels) / ((SELECT COUNT(*) FROM target) * totals.total) AS lift FROM tags t JOIN target ON target.zettel_id = t.zettel_id JOIN totals ON totals.tag = t.tag WHERE NOT (t.tag LIKE ?1 ESCAPE '\\' OR t.tag LIKE ?1 || '/%' ESCAPE '\\') GROUP BY t.tag ORDER BY count DESC, lift DESC;";
+        let mut tags = sqlx::query_as::<_, RelatedTag>(sql)
+            .bind(tag)
+            .fetch_all(conn)
+            .await?;
+        match options.sort {
+            Sort::Relevance => {}
+            Sort::Title => tags.sort_by_cached_key(|related| related.tag.to_lowercase()),
+            Sort::Created | Sort::Modified => {
+                anyhow::bail!("related tags can only be sorted by relevance or title")
+            }
+        }
+        Ok(page(tags, options))
+    }
+
+    /// Co-occurrence counts of every pair of tags in the wiki
+    pub async fn tag_matrix(conn: &mut SqliteConnection) -> Result<TagMatrix, anyhow::Error> {
+        let pairs = sqlx::query_as::<_, (String, String, i64)>("SELECT a.tag, b.tag, COUNT(DISTINCT a.zettel_id) FROM tags a JOIN tags b ON a.zettel_id = b.zettel_id GROUP BY a.tag, b.tag;")
+            .fetch_all(conn)
+            .await?;
+        let mut matrix = TagMatrix::new();
+        for (a, b, count) in pairs {
+            matrix.entry(a).or_default().insert(b, count);
+        }
+        Ok(matrix)
+    }
+
+    /// Paths of every zettel tagged with any of `tags` or a tag nested under them
+    pub async fn paths_with_tags(
+        conn: &mut SqliteConnection,
+        tags: &[String],
+    ) -> Result<Vec<String>, anyhow::Error> {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+        let condition =
+            vec!["(tag LIKE ? ESCAPE '\\' OR tag LIKE ? || '/%' ESCAPE '\\')"; tags.len()]
+                .join(" OR ");
+        let sql = format!("SELECT DISTINCT file_path FROM tags t JOIN zettels z ON z.zettel_id = t.zettel_id WHERE {};", condition);
+        let mut query = sqlx::query_as::<_, (String,)>(&sql);
+        for tag in tags {
+            let tag = like_escape(tag);
+            query = query.bind(tag.clone()).bind(tag);
+        }
+        Ok(query
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .map(|(path,)| path)
+            .collect())
+    }
+
+    /// Every tag in the tag hierarchy with usage counts, ordered so nested tags
+    /// directly follow their parent, or most recently used first when sorting by `modified`
+    pub async fn tag_index(
+        conn: &mut SqliteConnection,
+        options: &QueryOptions,
+    ) -> Result<Vec<TagSummary>, anyhow::Error> {
+        let mut tags = sqlx::query_as::<_, TagSummary>("SELECT h.tag, h.parent, COUNT(DISTINCT CASE WHEN h.tag = t.tag THEN t.zettel_id END) AS count, COUNT(DISTINCT t.zettel_id) AS total, MAX(max(z.timestamp, z.modified)) AS last_used FROM tag_hierarchy h LEFT JOIN tags t ON t.tag = h.tag COLLATE NOCASE OR (t.tag > h.tag || '/' COLLATE NOCASE AND t.tag < h.tag || '0' COLLATE NOCASE) LEFT JOIN zettels z ON z.zettel_id = t.zettel_id GROUP BY h.tag;")
+            .fetch_all(conn)
+            .await?;
+        tags.sort_by_cached_key(|summary| {
+            summary
+                .tag
+                .to_lowercase()
+                .split('/')
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        });
+        match options.sort {
+            Sort::Relevance | Sort::Title => {}
+            Sort::Modified => tags.sort_by_key(|summary| Reverse(summary.last_used)),
+            Sort::Created => anyhow::bail!("tags can not be sorted by created"),
+        }
+        Ok(page(tags, options))
+    }
+
+    /// Look up a zettel by its id, path (absolute or relative to the wiki), title, or file name without extension
+    pub async fn resolve(
+        conn: &mut SqliteConnection,
+        config: &Config,
+        note: &str,
+    ) -> Result<Zettel, anyhow::Error> {
+        let path = PathBuf::from(note);
+        let path = if path.is_absolute() {
+            path
+        } else {
+            config.wiki_location.join(path)
+        };
+        let path = path.to_string_lossy().into_owned();
+        let sql = format!("SELECT {} FROM zettels z WHERE z.zettel_id = ?1 OR file_path = ?2 OR title = ?1 COLLATE NOCASE OR file_path LIKE ?3 ESCAPE '\\' ORDER BY z.zettel_id = ?1 DESC, file_path = ?2 DESC, title = ?1 COLLATE NOCASE DESC LIMIT 1;", ZETTEL_COLUMNS);
+        let zettel = sqlx::query_as::<_, Zettel>(&sql)
+            .bind(note)
+            .bind(&path)
+            .bind(format!("%/{}.md", like_escape(note)))
+            .fetch_optional(conn)
+            .await?;
+        zettel.ok_or_else(|| anyhow::anyhow!("no zettel found for {}", note))
     }
 
     pub async fn get_by_path(
         conn: &mut SqliteConnection,
         path: &str,
     ) -> Result<Zettel, anyhow::Error> {
-        Ok(
-            sqlx::query_as::<_, Zettel>("SELECT zettel_id FROM zettels WHERE file_path = ?")
-                .bind(path)
-                .fetch_one(conn)
-                .await?,
-        )
+        Ok(sqlx::query_as::<_, Zettel>(&format!(
+            "SELECT {} FROM zettels z WHERE file_path = ?",
+            ZETTEL_COLUMNS
+        ))
+        .bind(path)
+        .fetch_one(conn)
+        .await?)
     }
 
     pub async fn latest_zettel(conn: &mut SqliteConnection) -> Result<Zettel, anyhow::Error> {
@@ -0,0 +1,171 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::Command;
+
+use regex::RegexBuilder;
+
+use crate::db::Zettel;
+
+/// Open a zettel from `zettels` in `$VISUAL` or `$EDITOR`.
+///
+/// When there are several and stdin is a terminal, a numbered pick list is shown on stderr,
+/// otherwise the top result is opened. The editor jumps to the first line containing a term
+/// highlighted in the zettel's snippet or `needle`.
+pub fn open(zettels: &[Zettel], needle: Option<&str>) -> Result<(), anyhow::Error> {
+    let zettel = match zettels {
+        [] => anyhow::bail!("no results to open"),
+        [zettel] => zettel,
+        _ if io::stdin().is_terminal() => pick(zettels)?,
+        _ => &zettels[0],
+    };
+    let line = matches(zettel, needle)
+        .into_iter()
+        .next()
+        .map(|found| found.line);
+    launch(&zettel.file_path, line)
+}
+
+fn pick(zettels: &[Zettel]) -> Result<&Zettel, anyhow::Error> {
+    let mut stderr = io::stderr();
+    for (index, zettel) in zettels.iter().enumerate() {
+        writeln!(
+            stderr,
+            "{}) {} ({})",
+            index + 1,
+            zettel.title,
+            zettel.file_path
+        )?;
+    }
+    write!(stderr, "Open which? [1]: ")?;
+    stderr.flush()?;
+    let mut choice = String::new();
+    io::stdin().lock().read_line(&mut choice)?;
+    let choice = choice.trim();
+    if choice.is_empty() {
+        return Ok(&zettels[0]);
+    }
+    choice
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| index.checked_sub(1))
+        .and_then(|index| zettels.get(index))
+        .ok_or_else(|| anyhow::anyhow!("{} is not one of the results", choice))
+}
+
+/// Where a term was found in a zettel
+pub struct Match {
+    /// Line, counting from 1
+    pub line: usize,
+    /// Byte offset into the line, counting from 1
+    pub column: usize,
+    /// The whole line
+    pub text: String,
+}
+
+/// Every place in a zettel's file where a term highlighted in its snippet, or the `needle`
+/// pattern, appears ignoring case, in the order they appear
+pub fn matches(zettel: &Zettel, needle: Option<&str>) -> Vec<Match> {
+    let mut terms = zettel
+        .snippet
+        .as_ref()
+        .map(|snippet| snippet.terms())
+        .unwrap_or_default();
+    terms.retain(|term| !term.trim().is_empty());
+    // Longest first so a term is not cut short by another it starts with
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    let patterns = needle
+        .filter(|needle| !needle.trim().is_empty())
+        .map(str::to_string)
+        .into_iter()
+        .chain(terms.iter().map(|term| regex::escape(term)))
+        .collect::<Vec<_>>();
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+    let pattern = patterns.join("|");
+    let (regex, content) = match (
+        RegexBuilder::new(&pattern).case_insensitive(true).build(),
+        fs::read_to_string(&zettel.file_path),
+    ) {
+        (Ok(regex), Ok(content)) => (regex, content),
+        _ => return Vec::new(),
+    };
+    content
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            regex
+                .find_iter(line)
+                .map(|found| Match {
+                    line: index + 1,
+                    column: found.start() + 1,
+                    text: line.to_string(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// A needle pattern for `#tag` and the tags nested under it, but not longer tags such as
+/// `#tagged`, or for every tag starting with `tag` when `prefix` is set
+pub fn tag_needle(tag: &str, prefix: bool) -> String {
+    let needle = format!("#{}", regex::escape(tag));
+    if prefix {
+        needle
+    } else {
+        format!(r"{}(?:[^\p{{L}}\p{{M}}\p{{N}}_\-]|$)", needle)
+    }
+}
+
+/// Run the editor on `path`, passing `line` the way the editor expects it
+fn launch(path: &str, line: Option<usize>) -> Result<(), anyhow::Error> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let mut command = Command::new(program);
+    command.args(words);
+    let name = Path::new(program)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match (line, name.as_str()) {
+        (Some(line), "code" | "codium" | "code-insiders") => {
+            command.arg("--goto").arg(format!("{}:{}", path, line))
+        }
+        (Some(line), "subl" | "zed" | "hx" | "helix") => command.arg(format!("{}:{}", path, line)),
+        (Some(line), _) => command.arg(format!("+{}", line)).arg(path),
+        (None, _) => command.arg(path),
+    };
+    let status = command.status()?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status)
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(needle: &str, line: &str) -> bool {
+        regex::Regex::new(needle).unwrap().is_match(line)
+    }
+
+    #[test]
+    fn tag_needle_stops_at_the_tag() {
+        let needle = tag_needle("go", false);
+        assert!(found(&needle, "learning #go"));
+        assert!(found(&needle, "#go, #rust"));
+        assert!(found(&needle, "#go/generics"));
+        assert!(!found(&needle, "#google"));
+        assert!(!found(&needle, "#go-kit"));
+        assert!(!found(&needle, "#go_lang"));
+        assert!(found(&tag_needle("go", true), "#google"));
+    }
+}
@@ -0,0 +1,138 @@
+/// Points for each character of the pattern found in the candidate
+const MATCH: i64 = 16;
+/// Bonus for a match directly following the previous match
+const CONSECUTIVE: i64 = 8;
+/// Bonus for a match at the start of a word
+const BOUNDARY: i64 = 8;
+/// Penalty for each skipped character between two matches
+const GAP: i64 = 1;
+/// Penalty for each character of the pattern missing from the candidate
+const TYPO: i64 = 24;
+/// Penalty for each character of the candidate after the last match, so shorter candidates rank first
+const UNMATCHED: i64 = 1;
+/// Bonus for a candidate that starts with the pattern
+const PREFIX: i64 = 32;
+/// Bonus for a candidate that is the pattern, ignoring case and spaces
+const EXACT: i64 = 64;
+
+/// Score `candidate` against `pattern` the way fzf does: every character of `pattern` must
+/// appear in `candidate` in order, with bonuses for consecutive runs and word starts.
+/// Exact and prefix matches rank above other matches, and shorter candidates above longer ones.
+///
+/// To tolerate typos, one character in four of `pattern` may be missing from `candidate`
+/// at a penalty. Returns `None` when `candidate` does not match.
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let pattern = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if pattern.is_empty() {
+        return None;
+    }
+    let normalized = candidate
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    let candidate = candidate.chars().collect::<Vec<_>>();
+    let allowed_typos = pattern.len() / 4;
+    let mut typos = 0;
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for &p in &pattern {
+        let found = candidate[position..]
+            .iter()
+            .position(|c| c.to_lowercase().eq(std::iter::once(p)))
+            .map(|offset| position + offset);
+        match found {
+            Some(index) => {
+                score += MATCH;
+                if is_boundary(&candidate, index) {
+                    score += BOUNDARY;
+                }
+                match previous {
+                    Some(prev) if prev + 1 == index => score += CONSECUTIVE,
+                    Some(prev) => score -= GAP * (index - prev - 1) as i64,
+                    None => score -= GAP * index as i64,
+                }
+                previous = Some(index);
+                position = index + 1;
+            }
+            None => {
+                typos += 1;
+                if typos > allowed_typos {
+                    return None;
+                }
+                score -= TYPO;
+            }
+        }
+    }
+    if normalized == pattern {
+        score += EXACT;
+    } else if normalized.starts_with(&pattern) {
+        score += PREFIX;
+    }
+    let trailing = candidate.len() - previous.map_or(0, |last| last + 1);
+    score -= UNMATCHED * trailing as i64;
+    Some(score)
+}
+
+/// Whether the character at `index` starts a word
+fn is_boundary(candidate: &[char], index: usize) -> bool {
+    match index.checked_sub(1).map(|prev| candidate[prev]) {
+        None => true,
+        Some(prev) => {
+            !prev.is_alphanumeric() || (prev.is_lowercase() && candidate[index].is_uppercase())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked<'a>(pattern: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+        let mut scored = candidates
+            .iter()
+            .filter_map(|candidate| Some((score(pattern, candidate)?, *candidate)))
+            .collect::<Vec<_>>();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, candidate)| candidate).collect()
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_shorter() {
+        assert_eq!(
+            ranked(
+                "note 12",
+                &["Note 1218", "My note 12", "Note 127", "Note 12"]
+            ),
+            ["Note 12", "Note 127", "Note 1218", "My note 12"]
+        );
+        assert_eq!(
+            ranked("rust", &["Trusting others", "Rust ownership", "Rust"]),
+            ["Rust", "Rust ownership", "Trusting others"]
+        );
+    }
+
+    #[test]
+    fn ignores_case_and_spaces() {
+        assert_eq!(score("NOTE12", "note 12"), score("note 12", "Note 12"));
+    }
+
+    #[test]
+    fn allows_one_typo_in_four() {
+        assert!(score("ownreship", "Ownership").is_some());
+        assert!(score("zettel", "Zetel").is_some());
+        assert!(score("abcd", "abxx").is_none());
+        assert!(score("abc", "ab").is_none());
+        assert!(score("zettel", "Zetel") < score("zettel", "Zettel"));
+    }
+
+    #[test]
+    fn rejects_empty_patterns() {
+        assert_eq!(score(" ", "Note"), None);
+    }
+}
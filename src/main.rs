@@ -1,10 +1,11 @@
-use arguments::{Config, Opts, SubCommand};
+use arguments::{Config, Opts, Periodic, SubCommand, TagCommand};
 use clap::StructOpt;
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use sqlx::{Connection, SqliteConnection};
 use std::ffi::OsStr;
 use std::fs::{self, File};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
 use std::time::Duration;
@@ -13,13 +14,25 @@ use std::time::Duration;
 pub mod arguments;
 /// CRUD ops for database
 pub mod db;
-use db::{edit, query};
+use db::{edit, query, Zettel};
+use note::Period;
+/// Open results in an editor
+pub mod editor;
+/// Fuzzy matching for quick-open
+pub mod fuzzy;
+/// Create zettels from templates
+pub mod note;
 /// Write out results
 pub mod output;
-use output::execute;
+/// Rename and merge tags inside zettels
+pub mod retag;
+use output::{execute, picked, related_tags, show, stream, tag_index, tag_matrix, written};
 
+/// `#tag` at the start of a line or after whitespace, `[` or `,`, the tag itself in group 1.
+/// Tags are Unicode letters and numbers plus `_`, `-` and `/` for nesting.
+/// `(#heading)` is a link to a heading, not a tag
 static TAGS_REGEX: Lazy<regex::Regex> =
-    Lazy::new(|| regex::Regex::new(r#"#[A-Za-z0-9-._]+"#).unwrap());
+    Lazy::new(|| regex::Regex::new(r#"(?m)(?:^|[\s\[,])#([\p{L}\p{M}\p{N}_\-/]+)"#).unwrap());
 static LINKS_REGEX: Lazy<regex::Regex> =
     Lazy::new(|| regex::Regex::new(r#"(\[([^\[]+)\]\((.*)\)|\[\[([^\[]+)\]\])"#).unwrap());
 static HEADERS_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
@@ -31,9 +44,9 @@ static HEADERS_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let opts = Opts::parse();
+    let mut opts = Opts::parse();
     let config: Config = {
-        let config_path = if let Some(dir) = opts.config_dir {
+        let config_path = if let Some(ref dir) = opts.config_dir {
             PathBuf::from(dir).join("config.toml")
         } else {
             PathBuf::from("config.toml")
@@ -51,14 +64,25 @@ async fn main() -> Result<(), anyhow::Error> {
             }
         }
     };
+    if let Some(template) = opts.output.template.as_mut() {
+        if let Some(named) = config.output_templates.get(template) {
+            *template = named.clone();
+        }
+    }
+    opts.output.links.get_or_insert(config.markdown_links);
+    opts.output
+        .relative_to
+        .get_or_insert_with(|| config.wiki_location.clone());
     let should_initialize = !Path::new("zettel.db").exists();
     if should_initialize {
         let _ = File::create("zettel.db")?;
     }
     let mut conn = SqliteConnection::connect("zettel.db").await?;
     if should_initialize {
-        edit::initialize_db(&mut conn).await?;
+        edit::initialize_db(&mut conn, &config).await?;
         edit::fill_db(&mut conn, &config, None).await?;
+    } else {
+        edit::upgrade(&mut conn, &config).await?;
     }
     if !should_initialize && opts.calculate {
         use chrono::prelude::*;
@@ -68,22 +92,90 @@ async fn main() -> Result<(), anyhow::Error> {
         };
         edit::fill_db(&mut conn, &config, Some(timestamp)).await?;
     }
+    opts.output.query = match opts.subcmd {
+        SubCommand::FullText(ref s) | SubCommand::Find(ref s) | SubCommand::Links(ref s) => {
+            Some(s.text.clone())
+        }
+        SubCommand::Tags(ref s) => Some(s.text.clone()),
+        _ => None,
+    };
+    opts.output.needle = match opts.subcmd {
+        SubCommand::Tags(ref s) => {
+            Some(editor::tag_needle(&edit::normalize_tag(&s.text), s.prefix))
+        }
+        SubCommand::Links(ref s) => Some(regex::escape(&s.text)),
+        _ => None,
+    };
     // If the DB didn't exist, we NEED to run create first
     match opts.subcmd {
         SubCommand::Create if !should_initialize => {
             edit::fill_db(&mut conn, &config, None).await?;
         }
         SubCommand::FullText(ref s) => {
-            let zettels = query::fulltext(&mut conn, &s.text).await?;
-            execute(zettels, &opts.format)?;
+            let mut zettels = Vec::new();
+            query::fulltext(&mut conn, &s.text, &config, &s.options, |zettel| {
+                collect(&opts, &mut zettels, zettel)
+            })
+            .await?;
+            results(zettels, &opts)?;
+        }
+        SubCommand::Find(ref s) => {
+            let mut zettels = Vec::new();
+            query::find(&mut conn, &s.text, &s.options, |zettel| {
+                collect(&opts, &mut zettels, zettel)
+            })
+            .await?;
+            results(zettels, &opts)?;
         }
         SubCommand::Tags(ref s) => {
-            let zettels = query::tags(&mut conn, &s.text).await?;
-            execute(zettels, &opts.format)?;
+            let mut zettels = Vec::new();
+            query::tags(&mut conn, &s.text, s.prefix, &s.options, |zettel| {
+                collect(&opts, &mut zettels, zettel)
+            })
+            .await?;
+            results(zettels, &opts)?;
         }
+        SubCommand::TagIndex(ref t) => {
+            let tags = query::tag_index(&mut conn, &t.options).await?;
+            tag_index(tags, &opts.format)?;
+        }
+        SubCommand::RelatedTags(ref r) => {
+            let tags = query::related_tags(&mut conn, &r.tag, &r.options).await?;
+            related_tags(tags, &opts.format)?;
+        }
+        SubCommand::TagMatrix => {
+            let matrix = query::tag_matrix(&mut conn).await?;
+            tag_matrix(matrix, &opts.format)?;
+        }
+        SubCommand::Tag(ref t) => match t.subcmd {
+            TagCommand::Rename(ref r) => {
+                retag::retag(&mut conn, std::slice::from_ref(&r.old), &r.new, r.dry_run).await?;
+            }
+            TagCommand::Merge(ref m) => {
+                retag::retag(&mut conn, &m.tags, &m.into, m.dry_run).await?;
+            }
+        },
         SubCommand::Links(ref s) => {
-            let zettels = query::links(&mut conn, &s.text).await?;
-            execute(zettels, &opts.format)?;
+            let mut zettels = Vec::new();
+            query::links(&mut conn, &s.text, &s.options, |zettel| {
+                collect(&opts, &mut zettels, zettel)
+            })
+            .await?;
+            results(zettels, &opts)?;
+        }
+        SubCommand::Preview(ref p) => {
+            let note = picked(&p.note).unwrap_or_else(|| p.note.clone());
+            let zettel = query::resolve(&mut conn, &config, &note).await?;
+            if p.path {
+                println!("{}", zettel.file_path);
+            } else {
+                print!("{}", fs::read_to_string(&zettel.file_path)?);
+            }
+        }
+        SubCommand::Show(ref s) => {
+            let zettel = query::resolve(&mut conn, &config, &s.note).await?;
+            let details = query::details(&mut conn, zettel).await?;
+            show(details, &opts.format)?;
         }
         SubCommand::Update(ref u) => {
             if u.all {
@@ -100,6 +192,38 @@ async fn main() -> Result<(), anyhow::Error> {
             }
         }
         SubCommand::Create => return Ok(()),
+        SubCommand::New(ref n) => {
+            let path = note::new(
+                &config,
+                &n.title,
+                &n.tags,
+                n.template.as_deref(),
+                n.folder.as_deref(),
+            )?;
+            edit::fill_n(&mut conn, std::slice::from_ref(&path)).await?;
+            let zettel = query::get_by_path(&mut conn, &path.to_string_lossy()).await?;
+            written(zettel, &opts.format, &opts.output)?;
+        }
+        SubCommand::Capture(ref c) => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            if text.trim().is_empty() {
+                anyhow::bail!("nothing to capture on stdin")
+            }
+            let path = match c.to {
+                Some(ref note) => {
+                    PathBuf::from(query::resolve(&mut conn, &config, note).await?.file_path)
+                }
+                None => config.wiki_location.join(&config.inbox),
+            };
+            note::capture(&path, c.heading.as_deref(), &text)?;
+            edit::fill_n(&mut conn, std::slice::from_ref(&path)).await?;
+            let zettel = query::get_by_path(&mut conn, &path.to_string_lossy()).await?;
+            written(zettel, &opts.format, &opts.output)?;
+        }
+        SubCommand::Daily(ref p) => periodic(&mut conn, &config, Period::Day, p, &opts).await?,
+        SubCommand::Weekly(ref p) => periodic(&mut conn, &config, Period::Week, p, &opts).await?,
+        SubCommand::Monthly(ref p) => periodic(&mut conn, &config, Period::Month, p, &opts).await?,
         SubCommand::Watch => {
             watch(&mut conn, &config).await?;
         }
@@ -108,6 +232,42 @@ async fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Open one of the results in an editor when asked to, otherwise write them all out
+fn results(zettels: Vec<Zettel>, opts: &Opts) -> Result<(), anyhow::Error> {
+    if opts.open {
+        editor::open(&zettels, opts.output.needle.as_deref())
+    } else {
+        execute(zettels, &opts.format, &opts.output)
+    }
+}
+
+/// Keep a zettel found by a query for [results], unless the output format writes it out straight away
+fn collect(opts: &Opts, zettels: &mut Vec<Zettel>, zettel: Zettel) -> Result<(), anyhow::Error> {
+    if opts.open {
+        zettels.push(zettel);
+    } else if let Some(zettel) = stream(zettel, &opts.format, &opts.output)? {
+        zettels.push(zettel);
+    }
+    Ok(())
+}
+
+async fn periodic(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    period: Period,
+    args: &Periodic,
+    opts: &Opts,
+) -> Result<(), anyhow::Error> {
+    let date = args
+        .date
+        .unwrap_or_else(|| chrono::Local::today().naive_local());
+    let path = note::periodic(config, period, date)?;
+    edit::fill_n(conn, std::slice::from_ref(&path)).await?;
+    let zettel = query::get_by_path(conn, &path.to_string_lossy()).await?;
+    written(zettel, &opts.format, &opts.output)?;
+    Ok(())
+}
+
 async fn watch(conn: &mut SqliteConnection, config: &Config) -> Result<(), anyhow::Error> {
     // Create a channel to receive the events.
     let (tx, rx) = channel();
@@ -124,15 +284,24 @@ async fn watch(conn: &mut SqliteConnection, config: &Config) -> Result<(), anyho
 
     loop {
         match rx.recv() {
-            Ok(DebouncedEvent::Write(path)) if path.extension() == Some(OsStr::new("md")) => {
+            Ok(DebouncedEvent::Write(path))
+                if path.extension() == Some(OsStr::new("md")) && !config.is_template(&path) =>
+            {
                 edit::fill_n(conn, &[path]).await?;
             }
-            Ok(DebouncedEvent::NoticeWrite(path)) if path.extension() == Some(OsStr::new("md")) => {
+            Ok(DebouncedEvent::NoticeWrite(path))
+                if path.extension() == Some(OsStr::new("md")) && !config.is_template(&path) =>
+            {
                 edit::fill_n(conn, &[path]).await?;
             }
             Ok(DebouncedEvent::Remove(old)) if old.extension() == Some(OsStr::new("md")) => {
                 edit::remove(conn, &old).await?;
             }
+            Ok(DebouncedEvent::Rename(old, new))
+                if old.extension() == Some(OsStr::new("md")) && config.is_template(&new) =>
+            {
+                edit::remove(conn, &old).await?;
+            }
             Ok(DebouncedEvent::Rename(old, new)) if old.extension() == Some(OsStr::new("md")) => {
                 edit::namechange(conn, &old, &new).await?;
             }
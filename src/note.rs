@@ -0,0 +1,329 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use once_cell::unsync::OnceCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::arguments::{Config, PeriodicConfig};
+
+/// Template used when none is named
+const DEFAULT_TEMPLATE: &str = "# {{title}}\n\n{{tags}}\n";
+/// Template used for periodic notes when none is configured
+const DEFAULT_PERIODIC_TEMPLATE: &str = "# {{title}}\n\n[[{{previous}}]] | [[{{next}}]]\n";
+
+/// Create a zettel in `folder` of [config.wiki_location](crate::arguments::Config) named with
+/// [config.zettel_date_format](crate::arguments::Config), filled in from `template`.
+///
+/// Without a `template`, the folder's default from
+/// [config.folder_templates](crate::arguments::Config) is used, and failing that a bare title and tags.
+/// Returns the path of the new zettel.
+pub fn new(
+    config: &Config,
+    title: &str,
+    tags: &[String],
+    template: Option<&str>,
+    folder: Option<&Path>,
+) -> Result<PathBuf, anyhow::Error> {
+    let now = Local::now();
+    let id = formatted(
+        now.format(&config.zettel_date_format),
+        &config.zettel_date_format,
+    )?;
+    let folder = folder.unwrap_or(Path::new(""));
+    let directory = config.wiki_location.join(folder);
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("{}.md", id));
+    if path.exists() {
+        anyhow::bail!("{} already exists", path.display())
+    }
+    let template = match template.or_else(|| folder_template(config, folder)) {
+        Some(name) => load_template(config, name)?,
+        None => String::from(DEFAULT_TEMPLATE),
+    };
+    let variables = Variables::new(title, &id, now, tags);
+    fs::write(&path, render(&template, &variables))?;
+    Ok(path)
+}
+
+/// A date written out with a chrono `format` from the config, an error when `format` is not valid
+fn formatted(date: impl std::fmt::Display, format: &str) -> Result<String, anyhow::Error> {
+    let mut text = String::new();
+    write!(text, "{}", date).map_err(|_| anyhow::anyhow!("invalid date format {:?}", format))?;
+    Ok(text)
+}
+
+/// Length of time a periodic note covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// First day of the period containing `date`, weeks starting on Monday
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+        }
+    }
+
+    fn previous(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start.pred(),
+            Period::Week => start - Duration::weeks(1),
+            Period::Month => self.start(start.pred()),
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start.succ(),
+            Period::Week => start + Duration::weeks(1),
+            Period::Month => self.start(start + Duration::days(31)),
+        }
+    }
+
+    fn config(self, config: &Config) -> &PeriodicConfig {
+        match self {
+            Period::Day => &config.daily,
+            Period::Week => &config.weekly,
+            Period::Month => &config.monthly,
+        }
+    }
+
+    fn default_format(self) -> &'static str {
+        match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%G-W%V",
+            Period::Month => "%Y-%m",
+        }
+    }
+}
+
+/// Path of the `period` note covering `date`, created from its template when it doesn't exist yet.
+///
+/// Periodic notes live in their configured folder (`journal` by default), named with their configured format.
+/// `{{previous}}` and `{{next}}` in their template are the names of the neighbouring periods' notes.
+pub fn periodic(
+    config: &Config,
+    period: Period,
+    date: NaiveDate,
+) -> Result<PathBuf, anyhow::Error> {
+    let settings = period.config(config);
+    let format = settings
+        .format
+        .as_deref()
+        .unwrap_or(period.default_format());
+    let name = |date: NaiveDate| formatted(date.format(format), format);
+    let directory = config
+        .wiki_location
+        .join(settings.folder.as_deref().unwrap_or(Path::new("journal")));
+    let start = period.start(date);
+    let title = name(start)?;
+    let path = directory.join(format!("{}.md", title));
+    if path.exists() {
+        return Ok(path);
+    }
+    fs::create_dir_all(&directory)?;
+    let template = match &settings.template {
+        Some(template) => load_template(config, template)?,
+        None => String::from(DEFAULT_PERIODIC_TEMPLATE),
+    };
+    let previous = name(period.previous(start))?;
+    let next = name(period.next(start))?;
+    let date = Local
+        .from_local_datetime(&start.and_hms(12, 0, 0))
+        .earliest()
+        .unwrap_or_else(Local::now);
+    let mut variables = Variables::new(&title, &title, date, &[]);
+    variables.previous = Some(&previous);
+    variables.next = Some(&next);
+    fs::write(&path, render(&template, &variables))?;
+    Ok(path)
+}
+
+/// Append `text` beneath a timestamp line to the end of the zettel at `path`, or to the end of
+/// the section under `heading` when given. A missing heading is added at the end of the zettel,
+/// and a missing zettel is created.
+pub fn capture(path: &Path, heading: Option<&str>, text: &str) -> Result<(), anyhow::Error> {
+    let entry = format!(
+        "**{}**\n{}\n",
+        Local::now().format("%Y-%m-%d %H:%M"),
+        text.trim_end()
+    );
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        let title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        format!("# {}\n", title)
+    };
+    let mut lines = content.lines().map(str::to_string).collect::<Vec<_>>();
+    let section_end = heading.and_then(|heading| {
+        let (start, level) = lines.iter().enumerate().find_map(|(index, line)| {
+            header(line)
+                .filter(|(_, text)| text.eq_ignore_ascii_case(heading.trim()))
+                .map(|(level, _)| (index, level))
+        })?;
+        let end = lines[start + 1..]
+            .iter()
+            .position(|line| matches!(header(line), Some((other, _)) if other <= level))
+            .map(|offset| start + 1 + offset)
+            .unwrap_or_else(|| lines.len());
+        Some(end)
+    });
+    let mut insert_at = match (heading, section_end) {
+        (_, Some(end)) => end,
+        (Some(heading), None) => {
+            lines.push(String::new());
+            lines.push(format!("## {}", heading.trim()));
+            lines.len()
+        }
+        (None, None) => lines.len(),
+    };
+    // Leave one blank line between the entry and whatever came before
+    while insert_at > 0 && lines[insert_at - 1].trim().is_empty() {
+        lines.remove(insert_at - 1);
+        insert_at -= 1;
+    }
+    let mut block = vec![String::new()];
+    block.extend(entry.lines().map(str::to_string));
+    if insert_at < lines.len() {
+        block.push(String::new());
+    }
+    lines.splice(insert_at..insert_at, block);
+    fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+/// Level and text of a markdown header line
+fn header(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    if (1..=6).contains(&level) {
+        Some((level, text.trim()))
+    } else {
+        None
+    }
+}
+
+/// Default template of the closest enclosing folder listed in
+/// [config.folder_templates](crate::arguments::Config)
+fn folder_template<'a>(config: &'a Config, folder: &Path) -> Option<&'a str> {
+    folder.ancestors().find_map(|ancestor| {
+        config
+            .folder_templates
+            .iter()
+            .find(|(configured, _)| configured.as_path() == ancestor)
+            .map(|(_, template)| template.as_str())
+    })
+}
+
+/// Read the template `name` from [config.templates_location](crate::arguments::Config)
+pub fn load_template(config: &Config, name: &str) -> Result<String, anyhow::Error> {
+    let path = config.templates_location().join(format!("{}.md", name));
+    fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("template {} not found at {}: {}", name, path.display(), e))
+}
+
+/// Values available to placeholders in a template:
+///
+/// - `{{title}}`
+/// - `{{id}}`
+/// - `{{date}}`, or `{{date:<format>}}` with a chrono format such as `{{date:%Y-%m-%d %H:%M}}`
+/// - `{{tags}}`, as space separated `#tags`
+/// - `{{stdin}}`, everything piped in on stdin
+/// - `{{previous}}` and `{{next}}`, for periodic notes
+///
+/// The clipboard is deliberately not available.
+pub struct Variables<'a> {
+    pub title: &'a str,
+    pub id: &'a str,
+    pub date: DateTime<Local>,
+    pub tags: &'a [String],
+    pub previous: Option<&'a str>,
+    pub next: Option<&'a str>,
+    /// Only read when a template asks for it, so nothing blocks waiting on a terminal
+    stdin: OnceCell<String>,
+}
+
+impl<'a> Variables<'a> {
+    pub fn new(title: &'a str, id: &'a str, date: DateTime<Local>, tags: &'a [String]) -> Self {
+        Self {
+            title,
+            id,
+            date,
+            tags,
+            previous: None,
+            next: None,
+            stdin: OnceCell::new(),
+        }
+    }
+
+    fn get(&self, placeholder: &str) -> Option<String> {
+        let (name, argument) = match placeholder.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (placeholder, None),
+        };
+        match name {
+            "title" => Some(self.title.to_string()),
+            "id" => Some(self.id.to_string()),
+            "previous" => self.previous.map(str::to_string),
+            "next" => self.next.map(str::to_string),
+            "date" => {
+                let mut date = String::new();
+                write!(date, "{}", self.date.format(argument.unwrap_or("%Y-%m-%d"))).ok()?;
+                Some(date)
+            }
+            "stdin" => Some(
+                self.stdin
+                    .get_or_init(|| {
+                        let mut input = String::new();
+                        let _ = io::stdin().read_to_string(&mut input);
+                        input.trim_end().to_string()
+                    })
+                    .clone(),
+            ),
+            "tags" => Some(
+                self.tags
+                    .iter()
+                    .map(|tag| format!("#{}", tag.trim_start_matches('#')))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Replace every `{{variable}}` in `template`, leaving unknown variables and invalid date formats as written
+pub fn render(template: &str, variables: &Variables) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        let placeholder = &after[..end];
+        match variables.get(placeholder.trim()) {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
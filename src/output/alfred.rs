@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 
-use crate::db::Zettel;
+use crate::arguments::Fields;
+use crate::db::{RelatedTag, TagSummary, Zettel};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AlfredResults {
@@ -17,6 +20,42 @@ pub struct Item {
     arg: Option<String>,
     autocomplete: Option<String>,
     icon: Option<Icon>,
+    /// Text Alfred filters on instead of the title
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    match_on: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quicklookurl: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mods: Option<Mods>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<Text>,
+    /// Passed on to the rest of the workflow, `action` tells it what to do with `arg`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    variables: BTreeMap<String, String>,
+}
+
+/// Alternative actions while holding a modifier key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mods {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cmd: Option<Mod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alt: Option<Mod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mod {
+    subtitle: String,
+    arg: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    variables: BTreeMap<String, String>,
+}
+
+/// Text for copying with ⌘C and showing with ⌘L
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Text {
+    copy: String,
+    largetype: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,21 +65,186 @@ pub struct Icon {
     path: String,
 }
 
-impl From<Vec<Zettel>> for AlfredResults {
-    fn from(src: Vec<Zettel>) -> Self {
+impl AlfredResults {
+    /// Offer to create a note named after `query` when nothing was found
+    pub fn or_create(mut self, query: Option<&str>) -> Self {
+        match query {
+            Some(query) if self.items.is_empty() && !query.trim().is_empty() => {
+                self.items.push(Item {
+                    uid: None,
+                    item_type: String::from("default"),
+                    title: format!("Create note \u{201c}{}\u{201d}", query),
+                    subtitle: Some(String::from("No results")),
+                    arg: Some(query.to_string()),
+                    autocomplete: None,
+                    icon: None,
+                    match_on: None,
+                    quicklookurl: None,
+                    mods: None,
+                    text: None,
+                    variables: variables(&[("action", "create"), ("title", query)]),
+                });
+                self
+            }
+            _ => self,
+        }
+    }
+}
+
+fn variables(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+impl From<Zettel> for Item {
+    fn from(zettel: Zettel) -> Self {
+        let name = Path::new(&zettel.file_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| zettel.title.clone());
+        let wikilink = format!("[[{}]]", name);
+        let snippet = zettel.snippet.as_ref().map(|snippet| snippet.plain());
+        let match_on = std::iter::once(&zettel.title)
+            .chain(&zettel.aliases.0)
+            .cloned()
+            .chain(zettel.tags.0.iter().map(|tag| format!("#{}", tag)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let largetype = match snippet {
+            Some(ref snippet) => format!("{}\n\n{}", zettel.title, snippet),
+            None => zettel.title.clone(),
+        };
+        let subtitle = snippet.unwrap_or_else(|| zettel.file_path.clone());
+        let folder = Path::new(&zettel.file_path)
+            .parent()
+            .map(|parent| parent.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Item {
+            variables: variables(&[
+                ("action", "open"),
+                ("zettel_id", zettel.zettel_id.as_str()),
+                ("title", zettel.title.as_str()),
+                ("path", zettel.file_path.as_str()),
+            ]),
+            uid: Some(zettel.zettel_id),
+            item_type: String::from("file"),
+            title: zettel.title.clone(),
+            subtitle: Some(subtitle),
+            arg: Some(zettel.file_path.clone()),
+            autocomplete: Some(zettel.title),
+            icon: Some(Icon {
+                icon_type: String::from("filetype"),
+                path: zettel.file_path.clone(),
+            }),
+            match_on: Some(match_on),
+            quicklookurl: Some(zettel.file_path.clone()),
+            mods: Some(Mods {
+                cmd: Some(Mod {
+                    subtitle: format!("Copy {}", wikilink),
+                    arg: wikilink.clone(),
+                    variables: variables(&[("action", "copy")]),
+                }),
+                alt: Some(Mod {
+                    subtitle: format!("Reveal in {}", folder),
+                    arg: zettel.file_path,
+                    variables: variables(&[("action", "reveal")]),
+                }),
+            }),
+            text: Some(Text {
+                copy: wikilink,
+                largetype,
+            }),
+        }
+    }
+}
+
+impl AlfredResults {
+    /// Items for zettels, with `fields` of each zettel added to its workflow variables
+    pub fn zettels(src: Vec<Zettel>, fields: Option<&Fields>) -> Result<Self, anyhow::Error> {
         let items = src
             .into_iter()
-            .map(|zettel| Item {
-                uid: Some(zettel.zettel_id),
-                item_type: String::from("file"),
-                title: zettel.title.clone(),
-                subtitle: Some(zettel.file_path.clone()),
-                arg: Some(zettel.file_path.clone()),
-                autocomplete: Some(zettel.title),
-                icon: Some(Icon {
-                    icon_type: String::from("filetype"),
-                    path: zettel.file_path,
-                }),
+            .map(|zettel| -> Result<Item, anyhow::Error> {
+                let selected = match fields {
+                    Some(fields) => super::select(&zettel, fields)?,
+                    None => serde_json::Map::new(),
+                };
+                let mut item = Item::from(zettel);
+                for (field, value) in selected {
+                    let value = match value {
+                        serde_json::Value::String(text) => text,
+                        serde_json::Value::Null => String::new(),
+                        serde_json::Value::Array(values) => values
+                            .iter()
+                            .map(|value| {
+                                value
+                                    .as_str()
+                                    .map(str::to_string)
+                                    .unwrap_or_else(|| value.to_string())
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        value => value.to_string(),
+                    };
+                    item.variables.insert(field, value);
+                }
+                Ok(item)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { items })
+    }
+}
+
+impl From<Vec<TagSummary>> for AlfredResults {
+    fn from(src: Vec<TagSummary>) -> Self {
+        let items = src
+            .into_iter()
+            .map(|summary| {
+                let mut subtitle = format!("{} zettels", super::tag_counts(&summary));
+                if let Some(last_used) = summary.last_used {
+                    subtitle.push_str(&format!(", last used {}", super::format_date(last_used)));
+                }
+                Item {
+                    uid: Some(summary.tag.clone()),
+                    item_type: String::from("default"),
+                    title: format!("#{}", summary.tag),
+                    subtitle: Some(subtitle),
+                    arg: Some(summary.tag.clone()),
+                    autocomplete: Some(summary.tag.clone()),
+                    icon: None,
+                    match_on: None,
+                    quicklookurl: None,
+                    mods: None,
+                    text: None,
+                    variables: variables(&[("action", "tag"), ("tag", summary.tag.as_str())]),
+                }
+            })
+            .collect();
+        Self { items }
+    }
+}
+
+impl From<Vec<RelatedTag>> for AlfredResults {
+    fn from(src: Vec<RelatedTag>) -> Self {
+        let items = src
+            .into_iter()
+            .map(|related| Item {
+                uid: Some(related.tag.clone()),
+                item_type: String::from("default"),
+                title: format!("#{}", related.tag),
+                subtitle: Some(format!(
+                    "{} of {} zettels together, lift {:.2}",
+                    related.count, related.total, related.lift
+                )),
+                arg: Some(related.tag.clone()),
+                autocomplete: Some(related.tag.clone()),
+                icon: None,
+                match_on: None,
+                quicklookurl: None,
+                mods: None,
+                text: None,
+                variables: variables(&[("action", "tag"), ("tag", related.tag.as_str())]),
             })
             .collect();
         Self { items }
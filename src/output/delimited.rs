@@ -0,0 +1,44 @@
+use std::io::{self, Write};
+
+/// Field separator of a delimited format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Separator {
+    Comma,
+    Tab,
+}
+
+/// Write `headers` and `rows` to `out` as CSV (RFC 4180) or TSV
+pub fn write(
+    out: &mut impl Write,
+    headers: &[String],
+    rows: &[Vec<String>],
+    separator: Separator,
+) -> io::Result<()> {
+    let (delimiter, escape): (&str, fn(&str) -> String) = match separator {
+        Separator::Comma => (",", escape_csv),
+        Separator::Tab => ("\t", escape_tsv),
+    };
+    for row in std::iter::once(headers).chain(rows.iter().map(Vec::as_slice)) {
+        let fields = row.iter().map(|field| escape(field)).collect::<Vec<_>>();
+        writeln!(out, "{}", fields.join(delimiter))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+pub fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escape tabs, line breaks and backslashes in a TSV field
+pub fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
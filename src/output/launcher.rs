@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::arguments::OutFormat;
+use crate::db::Zettel;
+
+use super::table::single_line;
+
+/// Icon rofi shows beside each zettel, from the icon theme
+const ROFI_ICON: &str = "text-markdown";
+
+/// Where the dmenu format keeps the zettel id of each line it printed: the user's runtime
+/// or cache directory, falling back to a file named after the user in the temp directory
+fn dmenu_lines() -> PathBuf {
+    let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+    let dir = var("XDG_RUNTIME_DIR")
+        .or_else(|| var("XDG_CACHE_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))
+        .or_else(|| var("LOCALAPPDATA").map(PathBuf::from));
+    match dir {
+        Some(dir) => dir.join("zettel-utils").join("dmenu.tsv"),
+        None => env::temp_dir().join(format!(
+            "zettel-utils-dmenu-{}.tsv",
+            env::var("USER").unwrap_or_default()
+        )),
+    }
+}
+
+/// Print one zettel as soon as it is found in the rofi or fzf formats
+pub fn line(out: &mut impl Write, zettel: &Zettel, output_kind: &OutFormat) -> io::Result<()> {
+    match output_kind {
+        OutFormat::Rofi => writeln!(out, "{}", rofi(zettel)),
+        _ => writeln!(out, "{}", fzf(zettel)),
+    }
+}
+
+/// A row for rofi's script mode, filtered on the aliases and tags kept in `meta`.
+/// The path is handed back to the script in `$ROFI_INFO` when the row is picked
+fn rofi(zettel: &Zettel) -> String {
+    let meta = zettel
+        .aliases
+        .0
+        .iter()
+        .cloned()
+        .chain(zettel.tags.0.iter().map(|tag| format!("#{}", tag)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "{}\0icon\x1f{}\x1fmeta\x1f{}\x1finfo\x1f{}",
+        single_line(&zettel.title),
+        ROFI_ICON,
+        meta,
+        zettel.file_path
+    )
+}
+
+/// Tab separated id, title, tags and path, so `--with-nth 2..` hides the id
+/// and `--preview 'zettel-utils preview {1}'` shows the zettel
+fn fzf(zettel: &Zettel) -> String {
+    let tags = zettel
+        .tags
+        .0
+        .iter()
+        .map(|tag| format!("#{}", tag))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let title = single_line(&zettel.title);
+    let fields: [&str; 4] = [&zettel.zettel_id, &title, &tags, &zettel.file_path];
+    fields.join("\t")
+}
+
+/// Print the title of each zettel for dmenu, adding the file name to titles that are not unique.
+/// The line printed for each zettel is remembered so [picked] can find it again
+pub fn dmenu(zettels: &[Zettel]) -> Result<(), anyhow::Error> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for zettel in zettels {
+        *seen.entry(single_line(&zettel.title)).or_default() += 1;
+    }
+    let mut lines = String::new();
+    for zettel in zettels {
+        let title = single_line(&zettel.title);
+        let line = if seen[&title] > 1 {
+            let name = Path::new(&zettel.file_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            format!("{} ({})", title, name)
+        } else {
+            title
+        };
+        println!("{}", line);
+        lines.push_str(&format!("{}\t{}\n", line, zettel.zettel_id));
+    }
+    let path = dmenu_lines();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, lines)?;
+    Ok(())
+}
+
+/// Id of the zettel behind a line picked from the last dmenu output
+pub fn picked(line: &str) -> Option<String> {
+    let lines = fs::read_to_string(dmenu_lines()).ok()?;
+    lines.lines().find_map(|entry| {
+        let (shown, id) = entry.rsplit_once('\t')?;
+        (shown == line.trim_end_matches('\n')).then(|| id.to_string())
+    })
+}
+
+/// Print tags with a short description, one per line, for the launcher formats
+pub fn tags(tags: Vec<(String, String)>, output_kind: &OutFormat) {
+    for (tag, detail) in tags {
+        match output_kind {
+            OutFormat::Rofi => println!("#{}\0meta\x1f{}\x1finfo\x1f{}", tag, detail, tag),
+            OutFormat::Fzf => println!("{}\t#{}\t{}", tag, tag, detail),
+            _ => println!("#{}", tag),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::arguments::{GroupBy, LinkStyle, OutputOptions};
+use crate::db::Zettel;
+
+use super::table::single_line;
+
+/// Print zettels as a bulleted list of links to paste into a structure note.
+///
+/// Grouped by tag, headings are in alphabetical order with untagged zettels last.
+/// Grouped by date, the newest day comes first.
+pub fn links(zettels: &[Zettel], options: &OutputOptions) {
+    let item = |zettel: &Zettel| format!("- {}", link(zettel, options));
+    match options.group_by {
+        None => {
+            for zettel in zettels {
+                println!("{}", item(zettel));
+            }
+        }
+        Some(GroupBy::Tag) => {
+            let mut groups: BTreeMap<&str, Vec<&Zettel>> = BTreeMap::new();
+            let mut untagged = Vec::new();
+            for zettel in zettels {
+                if zettel.tags.0.is_empty() {
+                    untagged.push(zettel);
+                }
+                for tag in &zettel.tags.0 {
+                    groups.entry(tag.as_str()).or_default().push(zettel);
+                }
+            }
+            let headings = groups
+                .into_iter()
+                .map(|(tag, zettels)| (format!("#{}", tag), zettels))
+                .chain(Some(("Untagged".to_string(), untagged)).filter(|(_, z)| !z.is_empty()));
+            grouped(headings, item);
+        }
+        Some(GroupBy::Date) => {
+            let mut groups: BTreeMap<String, Vec<&Zettel>> = BTreeMap::new();
+            for zettel in zettels {
+                let day = Local.timestamp(zettel.timestamp, 0).format("%Y-%m-%d");
+                groups.entry(day.to_string()).or_default().push(zettel);
+            }
+            grouped(groups.into_iter().rev(), item);
+        }
+    }
+}
+
+/// Print each group of links beneath a second level heading
+fn grouped<'a>(
+    groups: impl Iterator<Item = (String, Vec<&'a Zettel>)>,
+    item: impl Fn(&Zettel) -> String,
+) {
+    for (index, (heading, zettels)) in groups.enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("## {}\n", heading);
+        for zettel in zettels {
+            println!("{}", item(zettel));
+        }
+    }
+}
+
+fn link(zettel: &Zettel, options: &OutputOptions) -> String {
+    let path = Path::new(&zettel.file_path);
+    match options.links.unwrap_or_default() {
+        LinkStyle::Wiki => {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_else(|| zettel.title.as_str().into());
+            format!("[[{}]]", name)
+        }
+        LinkStyle::Relative => {
+            let target = match options.relative_to {
+                Some(ref base) => relative(path, base),
+                None => zettel.file_path.clone(),
+            };
+            format!(
+                "[{}]({})",
+                zettel.title.replace('[', "\\[").replace(']', "\\]"),
+                target.replace(' ', "%20")
+            )
+        }
+    }
+}
+
+/// `path` from inside `base`, or `path` as is when it lies outside of it
+fn relative(path: &Path, base: &Path) -> String {
+    let stripped = path
+        .strip_prefix(base)
+        .ok()
+        .map(Path::to_path_buf)
+        .or_else(|| {
+            let path = path.canonicalize().ok()?;
+            let base = base.canonicalize().ok()?;
+            path.strip_prefix(base).ok().map(Path::to_path_buf)
+        });
+    stripped
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Print `headers` and `rows` as a markdown table
+pub fn table(headers: &[String], rows: &[Vec<String>]) {
+    let line = |row: &[String]| {
+        let cells = row
+            .iter()
+            .map(|cell| single_line(cell).replace('|', "\\|"))
+            .collect::<Vec<_>>();
+        format!("| {} |", cells.join(" | "))
+    };
+    println!("{}", line(headers));
+    println!("|{}", " --- |".repeat(headers.len()));
+    for row in rows {
+        println!("{}", line(row));
+    }
+}
@@ -1,25 +1,463 @@
 mod alfred;
-use serde_json::to_string_pretty;
+mod delimited;
+mod launcher;
+mod markdown;
+mod table;
+mod template;
+mod terminal;
+use chrono::prelude::*;
+use std::io::{self, ErrorKind, Write};
+use std::process;
 
-use crate::arguments::OutFormat;
-use crate::db::Zettel;
+use serde_json::{json, to_string, to_string_pretty, to_value, Map, Value};
+
+use crate::arguments::{Column, Fields, OutFormat, OutputOptions};
+use crate::db::{RelatedTag, TagMatrix, TagSummary, Zettel, ZettelDetails};
+use crate::editor;
 
 use self::alfred::AlfredResults;
+use self::delimited::{escape_csv, Separator};
+pub use self::launcher::picked;
+
+pub fn execute(
+    zettels: Vec<Zettel>,
+    output_kind: &OutFormat,
+    options: &OutputOptions,
+) -> Result<(), anyhow::Error> {
+    if options.template.is_some() {
+        return stream_all(&zettels, output_kind, options);
+    }
+    let columns = &options.columns;
+    match output_kind {
+        OutFormat::StdOut => terminal::results(&zettels, terminal::use_color(options.color)),
+        OutFormat::JSON => {
+            let zettels = zettels
+                .iter()
+                .map(|zettel| selected(zettel, options))
+                .collect::<Result<Vec<_>, _>>()?;
+            println!("{}", to_string_pretty(&zettels)?);
+        }
+        OutFormat::Alfred => {
+            let out = AlfredResults::zettels(zettels, options.fields.as_ref())?
+                .or_create(options.query.as_deref());
+            println!("{}", to_string_pretty(&out)?);
+        }
+        OutFormat::Csv | OutFormat::Tsv | OutFormat::Table => {
+            let headers = columns
+                .0
+                .iter()
+                .map(|column| column.to_string())
+                .collect::<Vec<_>>();
+            let rows = zettels
+                .iter()
+                .map(|zettel| {
+                    columns
+                        .0
+                        .iter()
+                        .map(|column| cell(zettel, *column, output_kind))
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            rows_out(&headers, &rows, output_kind)?;
+        }
+        OutFormat::Markdown => markdown::links(&zettels, options),
+        OutFormat::Dmenu => launcher::dmenu(&zettels)?,
+        OutFormat::Ndjson | OutFormat::Rofi | OutFormat::Fzf | OutFormat::Quickfix => {
+            stream_all(&zettels, output_kind, options)?
+        }
+    }
+    Ok(())
+}
+
+/// Write out a zettel as soon as a query finds it when `output_kind` is a streaming format
+/// or a template is used, otherwise hand it back to be written out with the rest
+pub fn stream(
+    zettel: Zettel,
+    output_kind: &OutFormat,
+    options: &OutputOptions,
+) -> Result<Option<Zettel>, anyhow::Error> {
+    match (output_kind, &options.template) {
+        (_, Some(_))
+        | (OutFormat::Ndjson, None)
+        | (OutFormat::Rofi, None)
+        | (OutFormat::Fzf, None)
+        | (OutFormat::Quickfix, None) => {
+            stream_all(std::slice::from_ref(&zettel), output_kind, options)?;
+            Ok(None)
+        }
+        _ => Ok(Some(zettel)),
+    }
+}
+
+/// Write zettels a line at a time in a streaming format or template
+fn stream_all(
+    zettels: &[Zettel],
+    output_kind: &OutFormat,
+    options: &OutputOptions,
+) -> Result<(), anyhow::Error> {
+    to_stdout(|out| {
+        zettels
+            .iter()
+            .try_for_each(|zettel| streamed(out, zettel, output_kind, options))
+    })
+}
+
+/// Write to a locked stdout.
+///
+/// Stops and exits successfully once whatever reads stdout goes away, as `| head -1` does
+fn to_stdout(
+    write: impl FnOnce(&mut io::StdoutLock) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match write(&mut out) {
+        Err(error)
+            if error
+                .downcast_ref::<io::Error>()
+                .is_some_and(|error| error.kind() == ErrorKind::BrokenPipe) =>
+        {
+            process::exit(0)
+        }
+        written => written,
+    }
+}
+
+/// Write one zettel in a streaming format or template
+fn streamed(
+    out: &mut impl Write,
+    zettel: &Zettel,
+    output_kind: &OutFormat,
+    options: &OutputOptions,
+) -> Result<(), anyhow::Error> {
+    match (output_kind, &options.template) {
+        (_, Some(template)) => writeln!(out, "{}", template::render(template, zettel))?,
+        (OutFormat::Ndjson, None) => writeln!(out, "{}", to_string(&selected(zettel, options)?)?)?,
+        (OutFormat::Quickfix, None) => quickfix(out, zettel, options)?,
+        _ => launcher::line(out, zettel, output_kind)?,
+    }
+    Ok(())
+}
+
+/// A zettel as JSON, with only the fields passed to `--fields` when there are any
+fn selected(zettel: &Zettel, options: &OutputOptions) -> Result<Value, anyhow::Error> {
+    Ok(match options.fields {
+        Some(ref fields) => Value::Object(select(zettel, fields)?),
+        None => to_value(zettel)?,
+    })
+}
+
+/// `fields` of a zettel by name, a zettel without a snippet giving null for it
+fn select(zettel: &Zettel, fields: &Fields) -> Result<Map<String, Value>, anyhow::Error> {
+    let mut all = match to_value(zettel)? {
+        Value::Object(all) => all,
+        _ => Map::new(),
+    };
+    Ok(fields
+        .0
+        .iter()
+        .map(|field| (field.clone(), all.remove(field).unwrap_or(Value::Null)))
+        .collect())
+}
+
+/// Print `path:line:column: text` for every match in a zettel, as read by Vim's quickfix list
+/// and Emacs' grep-mode. A zettel without a match in its file is listed at its first line
+fn quickfix(out: &mut impl Write, zettel: &Zettel, options: &OutputOptions) -> io::Result<()> {
+    let matches = editor::matches(zettel, options.needle.as_deref());
+    if matches.is_empty() {
+        writeln!(out, "{}:1:1: {}", zettel.file_path, zettel.title)?;
+    }
+    for found in matches {
+        writeln!(
+            out,
+            "{}:{}:{}: {}",
+            zettel.file_path,
+            found.line,
+            found.column,
+            found.text.trim()
+        )?;
+    }
+    Ok(())
+}
 
-pub fn execute(zettels: Vec<Zettel>, output_kind: &OutFormat) -> Result<(), anyhow::Error> {
+/// Value of `column` for a zettel, with dates in RFC 3339 for csv and tsv
+fn cell(zettel: &Zettel, column: Column, output_kind: &OutFormat) -> String {
+    let date = |timestamp: i64| match output_kind {
+        OutFormat::Table => format_date(timestamp),
+        _ => Utc.timestamp(timestamp, 0).to_rfc3339(),
+    };
+    match column {
+        Column::Id => zettel.zettel_id.clone(),
+        Column::Title => zettel.title.clone(),
+        Column::Path => zettel.file_path.clone(),
+        Column::Created => date(zettel.timestamp),
+        Column::Modified => date(zettel.modified),
+        Column::Tags => zettel
+            .tags
+            .0
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Column::Snippet => zettel
+            .snippet
+            .as_ref()
+            .map(|snippet| snippet.plain())
+            .unwrap_or_default(),
+    }
+}
+
+/// Write out rows in one of the delimited or table formats
+fn rows_out(
+    headers: &[String],
+    rows: &[Vec<String>],
+    output_kind: &OutFormat,
+) -> Result<(), anyhow::Error> {
+    match output_kind {
+        OutFormat::Tsv => {
+            to_stdout(|out| Ok(delimited::write(out, headers, rows, Separator::Tab)?))?
+        }
+        OutFormat::Table => table::write(headers, rows),
+        OutFormat::Markdown => markdown::table(headers, rows),
+        _ => to_stdout(|out| Ok(delimited::write(out, headers, rows, Separator::Comma)?))?,
+    }
+    Ok(())
+}
+
+/// Write out a zettel that was just created or changed: its path on stdout, otherwise as a single result
+pub fn written(
+    zettel: Zettel,
+    output_kind: &OutFormat,
+    options: &OutputOptions,
+) -> Result<(), anyhow::Error> {
+    match (output_kind, &options.template) {
+        (OutFormat::StdOut, None) => println!("{}", zettel.file_path),
+        _ => execute(vec![zettel], output_kind, options)?,
+    }
+    Ok(())
+}
+
+/// Zettels with a tag, followed by those with it or a tag nested under it when that differs
+fn tag_counts(summary: &TagSummary) -> String {
+    if summary.total == summary.count {
+        summary.count.to_string()
+    } else {
+        format!("{}, {} with nested tags", summary.count, summary.total)
+    }
+}
+
+/// Write out every tag in use, nested tags indented beneath their parent on stdout
+pub fn tag_index(tags: Vec<TagSummary>, output_kind: &OutFormat) -> Result<(), anyhow::Error> {
+    match output_kind {
+        OutFormat::StdOut => {
+            println!("{} tags", tags.len());
+            for summary in tags {
+                let depth = summary.tag.matches('/').count();
+                let name = summary.tag.rsplit('/').next().unwrap_or(&summary.tag);
+                print!("{}#{} ({})", "  ".repeat(depth), name, tag_counts(&summary));
+                match summary.last_used {
+                    Some(last_used) => println!(" {}", format_date(last_used)),
+                    None => println!(),
+                }
+            }
+        }
+        OutFormat::JSON => println!("{}", to_string_pretty(&tags)?),
+        OutFormat::Ndjson => {
+            for summary in tags {
+                println!("{}", to_string(&summary)?);
+            }
+        }
+        OutFormat::Alfred => {
+            let out: AlfredResults = tags.into();
+            println!("{}", to_string_pretty(&out)?);
+        }
+        OutFormat::Rofi | OutFormat::Dmenu | OutFormat::Fzf => {
+            let tags = tags
+                .into_iter()
+                .map(|summary| {
+                    let detail = format!("{} zettels", tag_counts(&summary));
+                    (summary.tag, detail)
+                })
+                .collect();
+            launcher::tags(tags, output_kind);
+        }
+        OutFormat::Quickfix => anyhow::bail!("tags can not be output to quickfix"),
+        OutFormat::Csv | OutFormat::Tsv | OutFormat::Table | OutFormat::Markdown => {
+            let headers = ["tag", "parent", "count", "total", "last_used"].map(String::from);
+            let rows = tags
+                .iter()
+                .map(|summary| {
+                    vec![
+                        summary.tag.clone(),
+                        summary.parent.clone().unwrap_or_default(),
+                        summary.count.to_string(),
+                        summary.total.to_string(),
+                        summary.last_used.map(format_date).unwrap_or_default(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            rows_out(&headers, &rows, output_kind)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write out the tags found alongside another tag
+pub fn related_tags(tags: Vec<RelatedTag>, output_kind: &OutFormat) -> Result<(), anyhow::Error> {
     match output_kind {
         OutFormat::StdOut => {
-            println!("{} results", zettels.len());
-            println!("Title,Path");
-            for zettel in zettels {
-                println!("{},{}", zettel.title, zettel.file_path);
+            println!("{} tags", tags.len());
+            println!("Tag,Together,Total,Lift");
+            for related in tags {
+                println!(
+                    "{},{},{},{:.2}",
+                    escape_csv(&related.tag),
+                    related.count,
+                    related.total,
+                    related.lift
+                );
+            }
+        }
+        OutFormat::JSON => println!("{}", to_string_pretty(&tags)?),
+        OutFormat::Ndjson => {
+            for related in tags {
+                println!("{}", to_string(&related)?);
             }
         }
-        OutFormat::JSON => println!("{}", to_string_pretty(&zettels)?),
         OutFormat::Alfred => {
-            let out: AlfredResults = zettels.into();
+            let out: AlfredResults = tags.into();
             println!("{}", to_string_pretty(&out)?);
         }
+        OutFormat::Rofi | OutFormat::Dmenu | OutFormat::Fzf => {
+            let tags = tags
+                .into_iter()
+                .map(|related| {
+                    let detail = format!("{} of {} zettels together", related.count, related.total);
+                    (related.tag, detail)
+                })
+                .collect();
+            launcher::tags(tags, output_kind);
+        }
+        OutFormat::Quickfix => anyhow::bail!("tags can not be output to quickfix"),
+        OutFormat::Csv | OutFormat::Tsv | OutFormat::Table | OutFormat::Markdown => {
+            let headers = ["tag", "together", "total", "lift"].map(String::from);
+            let rows = tags
+                .iter()
+                .map(|related| {
+                    vec![
+                        related.tag.clone(),
+                        related.count.to_string(),
+                        related.total.to_string(),
+                        format!("{:.2}", related.lift),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            rows_out(&headers, &rows, output_kind)?;
+        }
     }
     Ok(())
 }
+
+/// Write out the tag co-occurrence matrix, as CSV on stdout
+pub fn tag_matrix(matrix: TagMatrix, output_kind: &OutFormat) -> Result<(), anyhow::Error> {
+    match output_kind {
+        OutFormat::JSON => println!("{}", to_string_pretty(&matrix)?),
+        OutFormat::Ndjson => {
+            for (tag, counts) in &matrix {
+                println!("{}", json!({ "tag": tag, "counts": counts }));
+            }
+        }
+        OutFormat::Alfred
+        | OutFormat::Rofi
+        | OutFormat::Dmenu
+        | OutFormat::Fzf
+        | OutFormat::Quickfix => {
+            anyhow::bail!("the tag matrix can not be output to {}", output_kind)
+        }
+        OutFormat::StdOut
+        | OutFormat::Csv
+        | OutFormat::Tsv
+        | OutFormat::Table
+        | OutFormat::Markdown => {
+            let headers = std::iter::once(String::new())
+                .chain(matrix.keys().cloned())
+                .collect::<Vec<_>>();
+            let rows = matrix
+                .iter()
+                .map(|(tag, row)| {
+                    std::iter::once(tag.clone())
+                        .chain(
+                            matrix
+                                .keys()
+                                .map(|other| row.get(other).copied().unwrap_or(0).to_string()),
+                        )
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            rows_out(&headers, &rows, output_kind)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write out everything known about a zettel, as a panel of sections on stdout
+pub fn show(details: ZettelDetails, output_kind: &OutFormat) -> Result<(), anyhow::Error> {
+    match output_kind {
+        OutFormat::StdOut => {
+            let zettel = &details.zettel;
+            let time = |timestamp: i64| Local.timestamp(timestamp, 0).format("%Y-%m-%d %H:%M");
+            println!("{}", zettel.title);
+            println!("id        {}", zettel.zettel_id);
+            println!("path      {}", zettel.file_path);
+            println!("created   {}", time(zettel.timestamp));
+            println!("modified  {}", time(zettel.modified));
+            println!("words     {}", zettel.words);
+            if !zettel.tags.0.is_empty() {
+                let tags = zettel.tags.0.iter().map(|tag| format!("#{}", tag));
+                println!("tags      {}", tags.collect::<Vec<_>>().join(" "));
+            }
+            if !zettel.aliases.0.is_empty() {
+                println!("aliases   {}", zettel.aliases.0.join(", "));
+            }
+            if let Some(ref frontmatter) = details.frontmatter {
+                println!("\nFrontmatter");
+                for line in frontmatter.lines() {
+                    println!("  {}", line);
+                }
+            }
+            if !details.outline.is_empty() {
+                println!("\nOutline");
+                for header in &details.outline {
+                    let depth = header.level.max(1) as usize - 1;
+                    println!("  {}{}", "  ".repeat(depth), header.text);
+                }
+            }
+            let broken = details
+                .links
+                .iter()
+                .filter(|link| link.target.is_none())
+                .count();
+            println!("\nLinks ({}, {} broken)", details.links.len(), broken);
+            for link in &details.links {
+                match link.target {
+                    Some(ref target) => println!("  {} -> {}", link.link, target),
+                    None => println!("  {} (broken)", link.link),
+                }
+            }
+            println!("\nBacklinks ({})", details.backlinks.len());
+            for backlink in &details.backlinks {
+                println!("  {} ({})", backlink.title, backlink.file_path);
+                if let Some(ref context) = backlink.context {
+                    println!("    {}", context);
+                }
+            }
+        }
+        OutFormat::JSON => println!("{}", to_string_pretty(&details)?),
+        OutFormat::Ndjson => println!("{}", to_string(&details)?),
+        _ => anyhow::bail!("a single zettel can not be shown as {}", output_kind),
+    }
+    Ok(())
+}
+
+pub(crate) fn format_date(timestamp: i64) -> String {
+    Utc.timestamp(timestamp, 0).format("%Y-%m-%d").to_string()
+}
@@ -0,0 +1,26 @@
+/// Print `headers` and `rows` as left aligned columns for reading in a terminal
+pub fn write(headers: &[String], rows: &[Vec<String>]) {
+    let rows = std::iter::once(headers)
+        .chain(rows.iter().map(Vec::as_slice))
+        .map(|row| row.iter().map(|cell| single_line(cell)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut widths = vec![0; headers.len()];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// Collapse line breaks and runs of whitespace into single spaces
+pub fn single_line(cell: &str) -> String {
+    cell.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,85 @@
+use chrono::prelude::*;
+
+use crate::db::Zettel;
+
+/// Fill in a user defined output template for a zettel.
+///
+/// `{field}` is replaced with one of `id`, `title`, `path`, `filename`, `created`, `modified`,
+/// `tags`, `aliases`, `words`, `links_in`, `links_out` or `snippet`, and dates take a chrono format as in `{created:%Y-%m-%d}`.
+/// `{{` and `}}` write literal braces and unknown fields are left as written.
+pub fn render(template: &str, zettel: &Zettel) -> String {
+    let mut rendered = String::new();
+    let mut chars = template.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                chars.next();
+                rendered.push('{');
+            }
+            '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                chars.next();
+                rendered.push('}');
+            }
+            '{' => match template[index + 1..].find('}') {
+                Some(end) => {
+                    let placeholder = &template[index + 1..index + 1 + end];
+                    match field(zettel, placeholder) {
+                        Some(value) => rendered.push_str(&value),
+                        None => rendered.push_str(&template[index..index + end + 2]),
+                    }
+                    while chars
+                        .next_if(|(next, _)| *next <= index + end + 1)
+                        .is_some()
+                    {}
+                }
+                None => rendered.push(c),
+            },
+            _ => rendered.push(c),
+        }
+    }
+    rendered
+}
+
+fn field(zettel: &Zettel, placeholder: &str) -> Option<String> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name.trim(), format),
+        None => (placeholder.trim(), "%Y-%m-%d %H:%M"),
+    };
+    let date = |timestamp: i64| {
+        use std::fmt::Write;
+        let mut date = String::new();
+        write!(date, "{}", Local.timestamp(timestamp, 0).format(format)).ok()?;
+        Some(date)
+    };
+    match name {
+        "id" => Some(zettel.zettel_id.clone()),
+        "title" => Some(zettel.title.clone()),
+        "path" => Some(zettel.file_path.clone()),
+        "filename" => std::path::Path::new(&zettel.file_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned()),
+        "created" => date(zettel.timestamp),
+        "modified" => date(zettel.modified),
+        "tags" => Some(
+            zettel
+                .tags
+                .0
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        "aliases" => Some(zettel.aliases.0.join(", ")),
+        "words" => Some(zettel.words.to_string()),
+        "links_in" => Some(zettel.links_in.to_string()),
+        "links_out" => Some(zettel.links_out.to_string()),
+        "snippet" => Some(
+            zettel
+                .snippet
+                .as_ref()
+                .map(|snippet| snippet.plain())
+                .unwrap_or_default(),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use terminal_size::{terminal_size, Width};
+
+use crate::arguments::ColorChoice;
+use crate::db::{Zettel, MATCH_END, MATCH_START};
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const MATCH: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+/// Gap between columns
+const GAP: usize = 2;
+
+/// Whether stdout should be colored, following `--color` and the `NO_COLOR` convention
+pub fn use_color(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    }
+}
+
+/// Columns of the terminal stdout is writing to, `None` when it is piped
+fn width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    match terminal_size() {
+        Some((Width(columns), _)) if columns > 0 => Some(usize::from(columns)),
+        _ => env::var("COLUMNS").ok()?.parse().ok(),
+    }
+}
+
+/// Print zettels as a table of title, tags and path with the snippet of each beneath it.
+///
+/// In a terminal, titles and paths are shortened to fit its width, paths losing their
+/// start so the file name stays visible.
+pub fn results(zettels: &[Zettel], color: bool) {
+    let paint = |style: &str, text: &str| {
+        if color && !text.is_empty() {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+    println!("{} results", zettels.len());
+    let rows = zettels
+        .iter()
+        .map(|zettel| {
+            let tags = zettel
+                .tags
+                .0
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" ");
+            (super::table::single_line(&zettel.title), tags)
+        })
+        .collect::<Vec<_>>();
+    let mut title_width = rows
+        .iter()
+        .map(|(title, _)| title.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut tags_width = rows
+        .iter()
+        .map(|(_, tags)| tags.chars().count())
+        .max()
+        .unwrap_or(0);
+    let width = width();
+    let mut path_width = None;
+    if let Some(width) = width {
+        title_width = title_width.min(width / 2);
+        tags_width = tags_width.min(width / 4);
+        path_width = Some((width.saturating_sub(title_width + tags_width + 2 * GAP)).max(16));
+    }
+    for (zettel, (title, tags)) in zettels.iter().zip(&rows) {
+        let title = truncate_end(title, title_width);
+        let tags = truncate_end(tags, tags_width);
+        let path = match path_width {
+            Some(path_width) => truncate_start(&zettel.file_path, path_width),
+            None => zettel.file_path.clone(),
+        };
+        let line = format!(
+            "{}{}{}{}{}",
+            paint(BOLD, &title),
+            pad(&title, title_width + GAP),
+            paint(CYAN, &tags),
+            pad(&tags, tags_width + GAP),
+            paint(DIM, &path),
+        );
+        println!("{}", line.trim_end());
+        if let Some(ref snippet) = zettel.snippet {
+            let marked = snippet.marked(&MATCH_START.to_string(), &MATCH_END.to_string());
+            let marked = match width {
+                Some(width) => truncate_end(&marked, width.saturating_sub(4)),
+                None => marked,
+            };
+            let (open, close) = if color { (MATCH, RESET) } else { ("", "") };
+            println!("    {}", highlight(&marked, open, close));
+        }
+    }
+}
+
+/// Spaces to pad `text` out to `width` characters
+fn pad(text: &str, width: usize) -> String {
+    " ".repeat(width.saturating_sub(visible_len(text)))
+}
+
+/// Characters of `text` that take up space, leaving out match markers
+fn visible_len(text: &str) -> usize {
+    text.chars()
+        .filter(|c| *c != MATCH_START && *c != MATCH_END)
+        .count()
+}
+
+/// `text` cut to `width` characters, ending with `…` when shortened
+fn truncate_end(text: &str, width: usize) -> String {
+    if visible_len(text) <= width {
+        return text.to_string();
+    }
+    let mut kept = String::new();
+    let mut count = 0;
+    let mut open = false;
+    for c in text.chars() {
+        if c == MATCH_START || c == MATCH_END {
+            open = c == MATCH_START;
+            kept.push(c);
+            continue;
+        }
+        if count + 1 >= width {
+            break;
+        }
+        kept.push(c);
+        count += 1;
+    }
+    if open {
+        kept.push(MATCH_END);
+    }
+    kept.push('…');
+    kept
+}
+
+/// `text` cut to `width` characters from the start, beginning with `…` when shortened
+fn truncate_start(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count <= width {
+        return text.to_string();
+    }
+    let kept = text
+        .chars()
+        .skip(count - width.saturating_sub(1))
+        .collect::<String>();
+    format!("…{}", kept)
+}
+
+/// Swap match markers for `open` and `close`
+fn highlight(text: &str, open: &str, close: &str) -> String {
+    text.replace(MATCH_START, open).replace(MATCH_END, close)
+}
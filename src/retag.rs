@@ -0,0 +1,316 @@
+use sqlx::SqliteConnection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::edit::{self, LineKind};
+use crate::db::query;
+
+/// Rewrite every `from` tag, and every tag nested under one, to `into` in each zettel using it.
+///
+/// Inline `#tags` and frontmatter `tags` lists are rewritten, code blocks and code spans are left alone.
+/// With `dry_run` set, a diff of each change is printed instead of written.
+pub async fn retag(
+    conn: &mut SqliteConnection,
+    from: &[String],
+    into: &str,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let from = from
+        .iter()
+        .map(|tag| edit::normalize_tag(tag))
+        .collect::<Vec<_>>();
+    let into = edit::normalize_tag(into);
+    if into.is_empty() || from.iter().any(String::is_empty) {
+        anyhow::bail!("tags must not be empty")
+    }
+    let mut changed = Vec::new();
+    for path in query::paths_with_tags(conn, &from).await? {
+        let path = PathBuf::from(path);
+        let content = fs::read_to_string(&path)?;
+        let lines = rewrite(&content, &from, &into);
+        let rewritten = joined(&lines);
+        if rewritten == content {
+            continue;
+        }
+        if dry_run {
+            print_diff(&path, &content, &lines);
+        } else {
+            fs::write(&path, rewritten)?;
+        }
+        changed.push(path);
+    }
+    if dry_run {
+        println!("{} zettels would be updated", changed.len());
+    } else {
+        edit::fill_n(conn, &changed).await?;
+        println!("{} zettels updated", changed.len());
+    }
+    Ok(())
+}
+
+/// Retag each line of a zettel in place, `None` for frontmatter tags dropped as duplicates
+fn rewrite(content: &str, from: &[String], into: &str) -> Vec<Option<String>> {
+    let mut tags: Option<TagsBlock> = None;
+    edit::line_kinds(content)
+        .map(|(kind, line)| match kind {
+            LineKind::Frontmatter if line.trim() != "---" => {
+                rewrite_frontmatter(line, &mut tags, from, into)
+            }
+            LineKind::Text => Some(rewrite_inline(line, from, into)),
+            _ => Some(line.to_string()),
+        })
+        .collect()
+}
+
+/// The contents of a zettel from its rewritten lines
+fn joined(lines: &[Option<String>]) -> String {
+    lines
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Tags seen so far in a frontmatter block list, and whether any of them was retagged
+#[derive(Default)]
+struct TagsBlock {
+    seen: Vec<String>,
+    retagged: bool,
+}
+
+/// Retag a frontmatter line that is, or belongs to, the `tags` key.
+///
+/// Once a tag is retagged, tags it now duplicates are dropped, `None` for a dropped list item
+fn rewrite_frontmatter(
+    line: &str,
+    tags: &mut Option<TagsBlock>,
+    from: &[String],
+    into: &str,
+) -> Option<String> {
+    let trimmed = line.trim_start();
+    if let (Some(block), true) = (tags.as_mut(), trimmed.starts_with('-')) {
+        let indent = &line[..line.len() - trimmed.len()];
+        let value = trimmed.trim_start_matches('-').trim();
+        let retagged = retag_value(value, from, into);
+        block.retagged |= retagged.is_some();
+        let name = tag_name(retagged.as_deref().unwrap_or(value));
+        if block.seen.contains(&name) {
+            return if block.retagged {
+                None
+            } else {
+                Some(line.to_string())
+            };
+        }
+        block.seen.push(name);
+        return Some(match retagged {
+            Some(value) => format!("{}- {}", indent, value),
+            None => line.to_string(),
+        });
+    }
+    *tags = None;
+    let rest = match line.strip_prefix("tags:") {
+        Some(rest) => rest.trim(),
+        None => return Some(line.to_string()),
+    };
+    Some(if rest.is_empty() {
+        *tags = Some(TagsBlock::default());
+        line.to_string()
+    } else if rest.starts_with('[') && rest.ends_with(']') {
+        let values = rest[1..rest.len() - 1]
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        if values
+            .iter()
+            .all(|value| retag_value(value, from, into).is_none())
+        {
+            return Some(line.to_string());
+        }
+        let mut seen = Vec::new();
+        let mut rewritten = Vec::new();
+        for value in values {
+            let value = retag_value(value, from, into).unwrap_or_else(|| value.to_string());
+            let name = tag_name(&value);
+            if !seen.contains(&name) {
+                seen.push(name);
+                rewritten.push(value);
+            }
+        }
+        format!("tags: [{}]", rewritten.join(", "))
+    } else {
+        match retag_value(rest, from, into) {
+            Some(value) => format!("tags: {}", value),
+            None => line.to_string(),
+        }
+    })
+}
+
+/// A frontmatter value as the tag it names, to compare tags however they are quoted or cased
+fn tag_name(value: &str) -> String {
+    edit::normalize_tag(&edit::unquote(value)).to_lowercase()
+}
+
+/// Retag a frontmatter value, keeping its quotes and `#`. Returns `None` when it is not retagged
+fn retag_value(value: &str, from: &[String], into: &str) -> Option<String> {
+    let quote = match value.chars().next() {
+        Some(c) if c == '"' || c == '\'' => c.to_string(),
+        _ => String::new(),
+    };
+    let inner = edit::unquote(value);
+    let hash = if inner.starts_with('#') { "#" } else { "" };
+    let retagged = retag_name(inner.trim_start_matches('#'), from, into)?;
+    Some(format!("{}{}{}{}", quote, hash, retagged, quote))
+}
+
+/// Retag every inline `#tag` of a line outside of code spans
+fn rewrite_inline(line: &str, from: &[String], into: &str) -> String {
+    line.split('`')
+        .enumerate()
+        .map(|(index, segment)| {
+            if index % 2 == 1 {
+                return segment.to_string();
+            }
+            let mut rewritten = String::new();
+            let mut last = 0;
+            for matched in edit::inline_tags(segment) {
+                let tag = edit::normalize_tag(matched.as_str());
+                if let Some(retagged) = retag_name(&tag, from, into) {
+                    rewritten.push_str(&segment[last..matched.start()]);
+                    rewritten.push_str(&matched.as_str().replacen(&tag, &retagged, 1));
+                    last = matched.end();
+                }
+            }
+            rewritten.push_str(&segment[last..]);
+            rewritten
+        })
+        .collect::<Vec<_>>()
+        .join("`")
+}
+
+/// `into` when `tag` is one of `from`, or `tag` moved under `into` when it is nested under one
+fn retag_name(tag: &str, from: &[String], into: &str) -> Option<String> {
+    let lowercase = tag.to_lowercase();
+    from.iter().find_map(|from| {
+        let from = from.to_lowercase();
+        if lowercase == from {
+            Some(into.to_string())
+        } else if lowercase.starts_with(&format!("{}/", from)) {
+            Some(format!("{}{}", into, tag.get(from.len()..)?))
+        } else {
+            None
+        }
+    })
+}
+
+fn print_diff(path: &Path, before: &str, after: &[Option<String>]) {
+    println!("--- {}", path.display());
+    println!("+++ {}", path.display());
+    let mut new_number = 0;
+    for (number, (old, new)) in before.split('\n').zip(after).enumerate() {
+        if new.as_deref() != Some(old) {
+            match new {
+                Some(new) => {
+                    println!("@@ -{} +{} @@", number + 1, new_number + 1);
+                    println!("-{}", old);
+                    println!("+{}", new);
+                }
+                None => {
+                    println!("@@ -{},1 +{},0 @@", number + 1, new_number);
+                    println!("-{}", old);
+                }
+            }
+        }
+        if new.is_some() {
+            new_number += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retagged(content: &str, from: &[&str], into: &str) -> String {
+        let from = from.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        joined(&rewrite(content, &from, into))
+    }
+
+    #[test]
+    fn retags_exact_and_nested_names() {
+        let from = ["Alpha".to_string()];
+        assert_eq!(retag_name("alpha", &from, "greek"), Some("greek".into()));
+        assert_eq!(
+            retag_name("alpha/beta", &from, "greek"),
+            Some("greek/beta".into())
+        );
+        assert_eq!(retag_name("alphabet", &from, "greek"), None);
+        assert_eq!(retag_name("beta/alpha", &from, "greek"), None);
+    }
+
+    #[test]
+    fn rewrites_inline_tags_outside_code_spans() {
+        let from = ["alpha".to_string()];
+        assert_eq!(
+            rewrite_inline("#alpha and #alpha/one, `#alpha` #alphabet", &from, "greek"),
+            "#greek and #greek/one, `#alpha` #alphabet"
+        );
+        assert_eq!(
+            rewrite_inline("end of #alpha.", &from, "greek"),
+            "end of #greek."
+        );
+        assert_eq!(
+            rewrite_inline("[alpha](#alpha) and [[note#alpha]]", &from, "greek"),
+            "[alpha](#alpha) and [[note#alpha]]"
+        );
+    }
+
+    #[test]
+    fn leaves_fenced_code_alone() {
+        let content = "#alpha\n```\n#alpha\n```\n~~~md\n#alpha\n~~~\n#alpha";
+        assert_eq!(
+            retagged(content, &["alpha"], "greek"),
+            "#greek\n```\n#alpha\n```\n~~~md\n#alpha\n~~~\n#greek"
+        );
+    }
+
+    #[test]
+    fn rewrites_frontmatter_lists_keeping_quotes() {
+        let content = "---\ntags: [alpha, \"#alpha/one\", 'beta']\n---\nbody";
+        assert_eq!(
+            retagged(content, &["alpha"], "greek"),
+            "---\ntags: [greek, \"#greek/one\", 'beta']\n---\nbody"
+        );
+        let content = "---\ntags:\n  - \"alpha\"\n  - beta\ntitle: alpha\n---\n#alpha";
+        assert_eq!(
+            retagged(content, &["alpha"], "greek"),
+            "---\ntags:\n  - \"greek\"\n  - beta\ntitle: alpha\n---\n#greek"
+        );
+        let content = "---\ntags: alpha\n---";
+        assert_eq!(
+            retagged(content, &["alpha"], "greek"),
+            "---\ntags: greek\n---"
+        );
+    }
+
+    #[test]
+    fn dedupes_merged_frontmatter_tags() {
+        let content = "---\ntags: [alpha, \"beta\", Greek]\n---";
+        assert_eq!(
+            retagged(content, &["alpha", "beta"], "greek"),
+            "---\ntags: [greek]\n---"
+        );
+        let content = "---\ntags:\n  - greek\n  - alpha\n  - 'beta'\n  - gamma\n---\nbody";
+        assert_eq!(
+            retagged(content, &["alpha", "beta"], "greek"),
+            "---\ntags:\n  - greek\n  - gamma\n---\nbody"
+        );
+    }
+
+    #[test]
+    fn keeps_duplicates_that_were_not_retagged() {
+        let content = "---\ntags:\n  - gamma\n  - gamma\n---";
+        assert_eq!(retagged(content, &["alpha"], "greek"), content);
+    }
+}